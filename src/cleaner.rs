@@ -0,0 +1,1146 @@
+use std::{iter, ops::Range};
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use whatlang::{detect, Lang};
+
+use crate::{
+    code::{detect_code, detect_code_blocks},
+    entity::EntityRecognizer,
+    html::{has_open_element, parse_html, HtmlText},
+    is_special_punctuation,
+    lang::apply_language,
+    link::LinkFinder,
+    log::LogNormalizer,
+    redact::Redactor,
+    reference::{link_reference, ReferenceFinder},
+    secret::SecretScrubber,
+    source::MappedText,
+    stats::TokenStats,
+    template::TemplateStripper,
+    trace::detect_traces,
+    AnchorPolicy, CleanStats, CleanerConfig, CodeBlockPolicy, CodeOrigin, CodeRegion, CodeSnippet,
+    DetectedLanguage, DropStats, Entity, EntityValue, JanitorError, LanguageMode, LanguageScope, Link, LinkPolicy,
+    MarkdownElement, PiiKind, Redaction, Reference, ReferenceKind, References, Secret, SecretKind, SpannedToken,
+    StackTrace, TagPolicy, Token, TracePolicy,
+};
+
+/// Cleans text according to a [`CleanerConfig`].
+#[derive(Debug, Clone)]
+pub struct Cleaner {
+    config: CleanerConfig,
+    templates: TemplateStripper,
+    links: LinkFinder,
+    logs: Option<LogNormalizer>,
+    entities: Option<EntityRecognizer>,
+    redactor: Option<Redactor>,
+    references: Option<ReferenceFinder>,
+    secrets: Option<SecretScrubber>,
+}
+
+impl Default for Cleaner {
+    fn default() -> Self {
+        Self::new(CleanerConfig::default())
+    }
+}
+
+impl Cleaner {
+    pub fn new(config: CleanerConfig) -> Self {
+        let templates = TemplateStripper::new(config.templates.clone());
+
+        Self {
+            templates,
+            links: LinkFinder::new(),
+            logs: config.normalize_logs.then(LogNormalizer::new),
+            entities: EntityRecognizer::new(&config.entities.enabled),
+            redactor: Redactor::new(&config.redaction.enabled, config.redaction.mode.clone()),
+            references: config.references.then(ReferenceFinder::new),
+            secrets: config.secrets.enabled.then(|| SecretScrubber::new(config.secrets.min_entropy)),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &CleanerConfig {
+        &self.config
+    }
+
+    pub fn clean_text_with_markdown(&self, text: &str) -> (Vec<String>, CleanStats) {
+        let cleaned = self.clean_tokens_with_markdown(text);
+        (self.render(&cleaned.tokens), cleaned.stats)
+    }
+
+    pub fn clean_text_with_html(&self, text: &str) -> (Vec<String>, CleanStats) {
+        let cleaned = self.clean_tokens_with_html(text);
+        (self.render(&cleaned.tokens), cleaned.stats)
+    }
+
+    pub fn clean_text(&self, input: &str) -> (Vec<String>, CleanStats) {
+        let cleaned = self.clean_tokens(input);
+        (self.render(&cleaned.tokens), cleaned.stats)
+    }
+
+    /// Renders tokens into the strings reported by the `clean_text*` methods.
+    pub fn render(&self, tokens: &[SpannedToken]) -> Vec<String> {
+        tokens
+            .iter()
+            .map(|t| t.token.render(&self.config).into_owned())
+            .collect()
+    }
+
+    pub fn try_clean_text_with_markdown(&self, text: &str) -> Result<(Vec<String>, CleanStats), JanitorError> {
+        let cleaned = self.try_clean_tokens_with_markdown(text)?;
+        Ok((self.render(&cleaned.tokens), cleaned.stats))
+    }
+
+    pub fn try_clean_text_with_html(&self, text: &str) -> Result<(Vec<String>, CleanStats), JanitorError> {
+        let cleaned = self.try_clean_tokens_with_html(text)?;
+        Ok((self.render(&cleaned.tokens), cleaned.stats))
+    }
+
+    /// Like [`Cleaner::try_clean_tokens_with_markdown`], falling back to
+    /// cleaning `text` as plain text if the markdown can't be processed.
+    pub fn clean_tokens_with_markdown(&self, text: &str) -> Cleaned {
+        self.try_clean_tokens_with_markdown(text)
+            .unwrap_or_else(|_| self.clean_tokens(text))
+    }
+
+    /// Like [`Cleaner::try_clean_tokens_with_html`], falling back to cleaning
+    /// `text` as plain text if the HTML can't be parsed.
+    pub fn clean_tokens_with_html(&self, text: &str) -> Cleaned {
+        self.try_clean_tokens_with_html(text)
+            .unwrap_or_else(|_| self.clean_tokens(text))
+    }
+
+    pub fn try_clean_tokens_with_markdown(&self, text: &str) -> Result<Cleaned, JanitorError> {
+        let mut out = Cleaned::default();
+        let (stripped, template_words) = self.templates.strip(text);
+        out.stats.dropped.template += template_words;
+        let text = &*stripped;
+
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+        let parsed = Parser::new_ext(text, options).into_offset_iter();
+
+        // one entry per open tag: the element and policy it was opened under,
+        // and where its tokens start
+        let mut stack: Vec<(Option<MarkdownElement>, TagPolicy, usize, Range<usize>)> = vec![];
+        let mut code_block: Option<(Option<String>, Range<usize>, MappedText)> = None;
+        // consecutive HTML events, their span and whether they are exactly
+        // the source in that span
+        let mut html: Option<(String, Range<usize>, bool)> = None;
+        // inside a paragraph already handled as code
+        let mut code_paragraph = false;
+        // code and stack traces found in the lines of the current paragraph,
+        // and the end of the one whose events are being skipped
+        let mut insets: Vec<Inset> = vec![];
+        let mut inset_end = 0;
+        for (evt, range) in parsed {
+            if let Some((buf, span, exact)) = &mut html {
+                match evt {
+                    Event::Html(_) => {}
+                    // inline content of an element opened by inline HTML, like
+                    // the text of `<code>...</code>`, is parsed along with it;
+                    // a code span stays inline code
+                    Event::Text(_) | Event::SoftBreak | Event::HardBreak if has_open_element(buf) =>
+                    {
+                        buf.push_str(text.get(range.clone()).unwrap_or_default());
+                        *exact &= span.end == range.start;
+                        span.end = range.end;
+                        continue;
+                    }
+                    _ => {
+                        let h = html.take().unwrap();
+                        self.push_html(&mut out, h)?;
+                    }
+                }
+            }
+
+            if let Some((_, _, code)) = &mut code_block {
+                match evt {
+                    Event::Text(t) => {
+                        if text.get(range.clone()) == Some(&*t) {
+                            code.push_exact(&t, range.start);
+                        } else {
+                            code.push_synthetic(&t, range);
+                        }
+                    }
+                    Event::End(Tag::CodeBlock(kind)) => {
+                        let (lang, span, code) = code_block.take().unwrap();
+                        let all = 0..code.text.len();
+                        self.push_code_block(&mut out, code_origin(&kind), lang, span, &code, all);
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if code_paragraph {
+                code_paragraph = !matches!(evt, Event::End(Tag::Paragraph));
+                continue;
+            }
+
+            // the innermost element whose content is being left out
+            let ignore = stack
+                .iter()
+                .rev()
+                .find(|(_, policy, _, _)| matches!(policy, TagPolicy::Ignore | TagPolicy::Replace))
+                .map(|(element, _, _, _)| *element);
+
+            // code lines and traces of a prose paragraph stand in for the
+            // events they cover, elements opened there are left out up to their end
+            if !matches!(evt, Event::End(_)) {
+                let starts = insets.first().is_some_and(|i| i.span().contains(&range.start));
+                if starts {
+                    let inset = insets.remove(0);
+                    let span = inset.span().clone();
+                    let src = MappedText::exact(&text[span.clone()], span.start);
+                    match inset {
+                        Inset::Code(region) => {
+                            let origin = CodeOrigin::Detected;
+                            self.push_code_block(&mut out, origin, None, span.clone(), &src, 0..span.len());
+                            out.code_regions.push(region);
+                        }
+                        Inset::Trace(trace) => self.push_trace(&mut out, trace, &src, 0..span.len()),
+                    }
+                    inset_end = span.end;
+                }
+                if range.start < inset_end {
+                    if let Event::Start(e) = evt {
+                        stack.push((MarkdownElement::of(&e), TagPolicy::Ignore, out.tokens.len(), range));
+                    }
+                    continue;
+                }
+            }
+
+            match evt {
+                Event::Start(e) => {
+                    let element = MarkdownElement::of(&e);
+                    let mut policy = element.map_or(TagPolicy::Keep, |el| self.config.markdown.policy(el));
+                    // links and images kept as such follow their anchor policy
+                    let anchor = self.config.markdown.anchor(&e).filter(|_| policy == TagPolicy::Keep);
+                    match anchor {
+                        Some(AnchorPolicy::Link) => policy = TagPolicy::Replace,
+                        Some(AnchorPolicy::Drop) => policy = TagPolicy::Ignore,
+                        _ => {}
+                    }
+                    stack.push((element, policy, out.tokens.len(), range.clone()));
+                    if ignore.is_some() {
+                        continue;
+                    }
+
+                    if matches!(e, Tag::Paragraph) {
+                        // a paragraph that is code as a whole is taken as one
+                        // block, code lines and traces in prose replace their
+                        // inline events
+                        let par = text[range.clone()].trim_end();
+                        let mut code = if self.config.detect_code { detect_code_blocks(par) } else { vec![] };
+                        let traces = match self.config.traces {
+                            TracePolicy::Keep => vec![],
+                            _ => detect_traces(par),
+                        };
+                        code.retain(|c| !traces.iter().any(|t| t.span.start < c.span.end && c.span.start < t.span.end));
+                        insets = code.into_iter().map(Inset::Code).collect();
+                        insets.extend(traces.into_iter().map(Inset::Trace));
+                        for inset in &mut insets {
+                            let span = inset.span_mut();
+                            *span = range.start + span.start..range.start + span.end;
+                        }
+                        insets.sort_by_key(|i| i.span().start);
+
+                        let whole = match insets.first() {
+                            Some(Inset::Code(r)) if r.span == (range.start..range.start + par.len()) => {
+                                Some(r.confidence)
+                            }
+                            _ => None,
+                        };
+                        if let Some(confidence) = whole {
+                            insets.clear();
+                            stack.pop();
+                            let span = range.start..range.start + par.len();
+                            let src = MappedText::exact(par, range.start);
+                            let origin = CodeOrigin::Detected;
+                            self.push_code_block(&mut out, origin, None, span.clone(), &src, 0..par.len());
+                            out.code_regions.push(CodeRegion { span, confidence });
+                            code_paragraph = true;
+                            continue;
+                        }
+                    }
+
+                    match (e, element) {
+                        (Tag::CodeBlock(kind), _) => {
+                            // the code block collects its own end tag
+                            stack.pop();
+                            let lang = match kind {
+                                CodeBlockKind::Fenced(info) => info
+                                    .split_whitespace()
+                                    .next()
+                                    .map(|l| l.trim_matches(',').to_lowercase()),
+                                CodeBlockKind::Indented => None,
+                            };
+                            code_block = Some((lang, range, MappedText::default()));
+                        }
+                        (Tag::Link(_, dest, _) | Tag::Image(_, dest, _), _)
+                            if anchor == Some(AnchorPolicy::Link) =>
+                        {
+                            self.push_destination(&mut out, &dest, range, self.config.links);
+                        }
+                        (_, Some(element)) if anchor == Some(AnchorPolicy::Marker) => {
+                            let token = match element {
+                                MarkdownElement::Image => Token::Image,
+                                _ => Token::Link,
+                            };
+                            self.push_token(&mut out, token, range);
+                        }
+                        (_, Some(element)) => match policy {
+                            TagPolicy::Replace => self.push_token(&mut out, Token::Element(element), range),
+                            TagPolicy::Section => self.push_token(&mut out, Token::Section(element), range),
+                            TagPolicy::Keep | TagPolicy::Ignore | TagPolicy::Label => {}
+                        },
+                        _ => {}
+                    }
+                }
+                Event::End(tag) => match stack.pop() {
+                    Some((Some(element), TagPolicy::Label, start, span)) if ignore.is_none() => {
+                        self.push_label(&mut out, element, start, span);
+                    }
+                    Some((_, TagPolicy::Keep, _, span)) if ignore.is_none() => {
+                        let anchor = self.config.markdown.anchor(&tag);
+                        if let (Some(AnchorPolicy::TextAndDomain), Tag::Link(_, dest, _) | Tag::Image(_, dest, _)) =
+                            (anchor, &tag)
+                        {
+                            if !Link::parse(dest).domain.is_empty() {
+                                self.push_destination(&mut out, dest, span, LinkPolicy::Domain);
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                Event::Text(t) => {
+                    if let Some(element) = ignore {
+                        count_dropped(&mut out.stats.dropped, element, t.split_whitespace().count());
+                        continue;
+                    }
+
+                    let mut src = MappedText::exact(&t, range.start);
+                    if text.get(range.clone()) != Some(&*t) {
+                        src.collapse(range.clone());
+                    }
+
+                    self.tokenize(&src, 0..src.text.len(), &mut out);
+                    self.push_token(&mut out, Token::Separator, range.end..range.end);
+                }
+                Event::Code(c) => {
+                    if let Some(element) = ignore {
+                        count_dropped(&mut out.stats.dropped, element, 1);
+                        continue;
+                    }
+
+                    out.stats.dropped.code += 1;
+                    self.push_token(&mut out, Token::Code, range.clone());
+                    if self.config.extract_code {
+                        // the code between the backticks, when it is in the source as is
+                        let at = text[range.clone()].find(&*c).map(|i| range.start + i);
+                        let within = |r: Range<usize>| at.map_or(range.clone(), |at| at + r.start..at + r.end);
+                        let snippet = code_snippet(CodeOrigin::Inline, None, c.to_string(), range.clone());
+                        self.extract_code(&mut out, snippet, true, within);
+                    }
+                },
+                Event::HardBreak|Event::SoftBreak => {
+                    if ignore.is_some() {
+                        continue;
+                    }
+
+                    self.push_token(&mut out, Token::Separator, range);
+                },
+                Event::Html(h) => {
+                    if ignore.is_some() {
+                        continue;
+                    }
+
+                    // an HTML block arrives one line at a time, and a comment
+                    // or element may span several lines or events
+                    let exact = text.get(range.clone()) == Some(&*h);
+                    match &mut html {
+                        Some((buf, span, contiguous)) => {
+                            buf.push_str(&h);
+                            *contiguous &= exact && span.end == range.start;
+                            span.end = range.end;
+                        }
+                        None => html = Some((h.to_string(), range, exact)),
+                    }
+                },
+                Event::Rule => {
+                    if ignore.is_some() {
+                        continue;
+                    }
+
+                    self.push_token(&mut out, Token::Separator, range);
+                },
+                // footnotes and task lists are not enabled in the parser options
+                Event::FootnoteReference(_) | Event::TaskListMarker(_) => {}
+            }
+        }
+
+        if let Some(h) = html {
+            self.push_html(&mut out, h)?;
+        }
+
+        Ok(self.finish(text, out))
+    }
+
+    pub fn try_clean_tokens_with_html(&self, text: &str) -> Result<Cleaned, JanitorError> {
+        let mut out = Cleaned::default();
+        self.tokenize_html(parse_html(text, self.config.keep_html_comments)?, &mut out);
+
+        Ok(self.finish(text, out))
+    }
+
+    pub fn clean_tokens(&self, input: &str) -> Cleaned {
+        let mut out = Cleaned::default();
+        let src = MappedText::exact(input, 0);
+        let mut last = 0;
+        if self.config.traces != TracePolicy::Keep {
+            for trace in detect_traces(input) {
+                self.tokenize_plain(&src, last..trace.span.start, &mut out);
+                last = trace.span.end;
+                let at = trace.span.clone();
+                self.push_trace(&mut out, trace, &src, at);
+            }
+        }
+        self.tokenize_plain(&src, last..input.len(), &mut out);
+
+        self.finish(input, out)
+    }
+
+    /// Tokenizes plain text, first taking out code when `detect_code` is set.
+    fn tokenize_plain(&self, src: &MappedText, range: Range<usize>, out: &mut Cleaned) {
+        let mut last = range.start;
+        if self.config.detect_code {
+            for mut region in detect_code_blocks(&src.text[range.clone()]) {
+                region.span = range.start + region.span.start..range.start + region.span.end;
+                self.tokenize(src, last..region.span.start, out);
+                let span = src.span(region.span.clone());
+                self.push_code_block(out, CodeOrigin::Detected, None, span.clone(), src, region.span.clone());
+                last = region.span.end;
+                region.span = span;
+                out.code_regions.push(region);
+            }
+        }
+        self.tokenize(src, last..range.end, out);
+    }
+
+    fn finish(&self, input: &str, mut out: Cleaned) -> Cleaned {
+        apply_language(&self.config.language, input, &mut out);
+        out.stats.emitted = TokenStats::count(&out.tokens);
+
+        out
+    }
+
+    /// Cleans HTML found in markdown, mapped back onto `span` of the source.
+    fn push_html(&self, out: &mut Cleaned, (h, span, exact): (String, Range<usize>, bool)) -> Result<(), JanitorError> {
+        let mut html = parse_html(&h, self.config.keep_html_comments)?;
+        if exact {
+            html.offset(span.start);
+        } else {
+            html.collapse(span.clone());
+        }
+
+        self.tokenize_html(html, out);
+        self.push_token(out, Token::Separator, span.end..span.end);
+
+        Ok(())
+    }
+
+    /// Applies the code block policy to `range` of `code`, found at `span`.
+    /// Stack traces in the block are summarized, and a block holding nothing
+    /// else leaves only the summaries.
+    fn push_code_block(
+        &self,
+        out: &mut Cleaned,
+        origin: CodeOrigin,
+        lang: Option<String>,
+        span: Range<usize>,
+        code: &MappedText,
+        range: Range<usize>,
+    ) {
+        let (traces, code_left) = self.find_traces(&code.text[range.clone()]);
+        if code_left {
+            self.push_code(out, origin, lang, span.clone(), code, range.clone());
+        }
+        for trace in traces {
+            let at = range.start + trace.span.start..range.start + trace.span.end;
+            self.push_trace(out, trace, code, at);
+        }
+    }
+
+    fn push_code(
+        &self,
+        out: &mut Cleaned,
+        origin: CodeOrigin,
+        lang: Option<String>,
+        span: Range<usize>,
+        code: &MappedText,
+        range: Range<usize>,
+    ) {
+        out.stats.dropped.code += 1;
+
+        let policy = self.config.code_blocks;
+        let extract = policy == CodeBlockPolicy::Verbatim || self.config.extract_code;
+        let text = &code.text[range.clone()];
+        if policy == CodeBlockPolicy::Drop {
+            if extract {
+                let snippet = code_snippet(origin, lang, text.to_owned(), span);
+                self.extract_code(out, snippet, false, |r| code.span(range.start + r.start..range.start + r.end));
+            }
+            return;
+        }
+
+        self.push_token(out, Token::CodeBlock(lang.clone()), span.clone());
+        if extract {
+            let snippet = code_snippet(origin, lang, text.to_owned(), span.clone());
+            self.extract_code(out, snippet, true, |r| code.span(range.start + r.start..range.start + r.end));
+        }
+
+        if policy == CodeBlockPolicy::Summarize {
+            let mut start = range.start;
+            for line in text.split_inclusive('\n') {
+                if !line.trim().is_empty() {
+                    self.tokenize(code, start..start + line.trim_end().len(), out);
+                    break;
+                }
+                start += line.len();
+            }
+        }
+        self.push_token(out, Token::Separator, span.end..span.end);
+    }
+
+    /// Stack traces in `text` under the trace policy, and whether anything but
+    /// whitespace is left around them.
+    fn find_traces(&self, text: &str) -> (Vec<StackTrace>, bool) {
+        let traces = match self.config.traces {
+            TracePolicy::Keep => vec![],
+            _ => detect_traces(text),
+        };
+        let mut rest = text.to_owned();
+        for t in &traces {
+            rest.replace_range(t.span.clone(), &" ".repeat(t.span.len()));
+        }
+
+        (traces, !rest.trim().is_empty())
+    }
+
+    /// Replaces a stack trace, found at `at` in `src`, with its language,
+    /// message and top frames.
+    fn push_trace(&self, out: &mut Cleaned, mut trace: StackTrace, src: &MappedText, at: Range<usize>) {
+        trace.span = src.span(at.clone());
+        let span = trace.span.clone();
+        out.stats.dropped.trace += 1;
+        self.push_token(out, Token::Trace(trace.lang), span.clone());
+
+        // the message where it is in the source, if it is there as is
+        let found = src.text[at.clone()].find(&trace.message);
+        let message = found.map(|i| at.start + i..at.start + i + trace.message.len());
+        match &message {
+            Some(r) => self.tokenize(src, r.clone(), out),
+            None => {
+                let mut synthetic = MappedText::default();
+                synthetic.push_synthetic(&trace.message, span.clone());
+                self.tokenize(&synthetic, 0..synthetic.text.len(), out);
+            }
+        }
+        for f in trace.frames.iter().take(self.config.trace_frames) {
+            self.push_token(out, Token::Frame(f.to_string()), span.clone());
+        }
+        self.push_token(out, Token::Separator, span.end..span.end);
+
+        if self.config.traces == TracePolicy::Structured {
+            let within = |r: Range<usize>| match &message {
+                Some(m) => src.span(m.start + r.start..m.start + r.end),
+                None => span.clone(),
+            };
+            trace.message = self.scrub(out, &trace.message, within);
+            out.traces.push(trace);
+        }
+    }
+
+    /// Folds the tokens pushed since `start` into a single label token.
+    fn push_label(&self, out: &mut Cleaned, element: MarkdownElement, start: usize, span: Range<usize>) {
+        let label = out
+            .tokens
+            .drain(start..)
+            .filter(|t| t.token != Token::Separator)
+            .map(|t| t.token.render(&self.config).into_owned())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        for position in out.positions_mut() {
+            *position = (*position).min(start);
+        }
+
+        self.push_token(out, Token::Label(element, label), span);
+    }
+
+    /// Records a snippet at the current end of the token stream, `placed` when
+    /// its placeholder is the last token. `within` maps a range of the snippet
+    /// text to the input.
+    fn extract_code(
+        &self,
+        out: &mut Cleaned,
+        mut snippet: CodeSnippet,
+        placed: bool,
+        within: impl Fn(Range<usize>) -> Range<usize>,
+    ) {
+        snippet.text = self.scrub(out, &snippet.text, within);
+        if let Some(logs) = &self.logs {
+            snippet.text = logs.normalize(&snippet.text);
+        }
+        snippet.position = if placed { out.tokens.len() - 1 } else { out.tokens.len() };
+        out.code.push(snippet);
+    }
+
+    fn tokenize_html(&self, html: HtmlText, out: &mut Cleaned) {
+        out.stats.dropped.markup += html.comment_words;
+        let src = &html.text;
+        let mut code = html.code.into_iter().peekable();
+        let mut last = 0;
+        let end = src.text.len();
+        // brace blocks become a code section, detected paragraphs follow the
+        // code block policy
+        let mut regions = detect_code(&src.text).into_iter().map(|r| (r, false)).collect::<Vec<_>>();
+        if self.config.detect_code {
+            regions.extend(detect_code_blocks(&src.text).into_iter().map(|r| (r, true)));
+            regions.sort_by_key(|(r, _)| r.span.start);
+            let mut covered = 0;
+            regions.retain(|(r, _)| {
+                let keep = r.span.start >= covered;
+                covered = covered.max(r.span.end);
+                keep
+            });
+        }
+        let regions = regions.into_iter().chain(iter::once((
+            CodeRegion {
+                span: end..end,
+                confidence: 0.0,
+            },
+            false,
+        )));
+        for (CodeRegion { span: region, confidence }, block) in regions {
+            while let Some(c) = code.next_if(|c| c.at <= region.start) {
+                let at = c.at.max(last);
+                self.tokenize(src, last..at, out);
+                last = at;
+
+                let (traces, code_left) = self.find_traces(&c.text);
+                let mut code_src = MappedText::default();
+                code_src.push_synthetic(&c.text, c.span.clone());
+                if code_left {
+                    out.stats.dropped.code += 1;
+                    self.push_token(out, Token::Code, c.span.clone());
+                    if self.config.extract_code {
+                        let snippet = code_snippet(CodeOrigin::Html, c.lang, c.text, c.span.clone());
+                        self.extract_code(out, snippet, true, |_| c.span.clone());
+                    }
+                }
+                for trace in traces {
+                    let at = trace.span.clone();
+                    self.push_trace(out, trace, &code_src, at);
+                }
+            }
+
+            self.tokenize(src, last..region.start, out);
+            if block {
+                let span = src.span(region.clone());
+                self.push_code_block(out, CodeOrigin::Detected, None, span.clone(), src, region.clone());
+                out.code_regions.push(CodeRegion { span, confidence });
+            } else if !region.is_empty() {
+                let span = src.span(region.clone());
+                out.stats.dropped.code += 1;
+                self.push_token(out, Token::CodeSection, span.clone());
+                if self.config.extract_code {
+                    let text = src.text[region.clone()].to_owned();
+                    let snippet = code_snippet(CodeOrigin::Detected, None, text, span.clone());
+                    let within = |r: Range<usize>| src.span(region.start + r.start..region.start + r.end);
+                    self.extract_code(out, snippet, true, within);
+                }
+                out.code_regions.push(CodeRegion { span, confidence });
+            }
+            last = region.end;
+        }
+    }
+
+    fn tokenize(&self, src: &MappedText, range: Range<usize>, out: &mut Cleaned) {
+        // at the same position secrets win over URLs, URLs over personal data,
+        // personal data over references, and all of them over entities
+        let text = &src.text[range.clone()];
+        let mut found = vec![];
+        if let Some(secrets) = &self.secrets {
+            found.extend(secrets.find(text).into_iter().map(|(r, kind)| (r, Replaced::Secret(kind))));
+        }
+        found.extend(self.links.find(text).into_iter().map(|r| (r, Replaced::Link)));
+        if let Some(redactor) = &self.redactor {
+            found.extend(redactor.find(text).into_iter().map(|(r, kind)| (r, Replaced::Pii(kind))));
+        }
+        if let Some(references) = &self.references {
+            let refs = references.find(text).into_iter();
+            found.extend(refs.map(|(r, kind, target)| (r, Replaced::Reference(kind, target))));
+        }
+        if let Some(logs) = &self.logs {
+            found.extend(logs.fields(&src.text, range.clone()).into_iter().map(|(r, e)| (r, Replaced::Entity(e))));
+        }
+        if let Some(entities) = &self.entities {
+            found.extend(entities.find(text).into_iter().map(|(r, e)| (r, Replaced::Entity(e))));
+        }
+        found.sort_by_key(|(r, _)| r.start);
+        // an entity starting earlier still never hides personal data or a
+        // secret inside it, like the user of a home directory path
+        let hidden = found
+            .iter()
+            .filter(|(_, f)| matches!(f, Replaced::Pii(_) | Replaced::Secret(_)))
+            .map(|(r, _)| r.clone())
+            .collect::<Vec<_>>();
+        found.retain(|(r, f)| {
+            !matches!(f, Replaced::Entity(_)) || !hidden.iter().any(|h| h.start < r.end && r.start < h.end)
+        });
+
+        let mut last = range.start;
+        for (r, found) in found {
+            let r = range.start + r.start..range.start + r.end;
+            if r.start < last {
+                continue;
+            }
+            self.tokenize_words(src, last..r.start, out);
+            match found {
+                Replaced::Entity(entity) => self.push_entity(out, entity, src, r.clone()),
+                Replaced::Link => self.push_link(out, src, r.clone(), self.config.links),
+                Replaced::Pii(kind) => self.push_redacted(out, kind, src, r.clone()),
+                Replaced::Reference(kind, target) => {
+                    out.tokens.push(SpannedToken {
+                        token: Token::Reference(kind),
+                        span: src.span(r.clone()),
+                        lang: None,
+                    });
+                    self.push_reference(out, kind, target, src.span(r.clone()));
+                }
+                Replaced::Secret(kind) => {
+                    let span = src.span(r.clone());
+                    self.push_token(out, Token::Redacted(kind.placeholder()), span.clone());
+                    out.secrets.push(Secret { kind, span });
+                }
+            }
+            last = r.end;
+        }
+        self.tokenize_words(src, last..range.end, out);
+    }
+
+    /// Pushes a link token for the destination of a markdown link or image.
+    fn push_destination(&self, out: &mut Cleaned, dest: &str, span: Range<usize>, policy: LinkPolicy) {
+        let mut src = MappedText::default();
+        src.push_synthetic(dest, span);
+        self.push_link(out, &src, 0..dest.len(), policy);
+    }
+
+    /// Pushes the token for a URL, falling back to the placeholder for a
+    /// relative link rendered by domain. Links to issues, pull requests and
+    /// commits become references when those are collected.
+    fn push_link(&self, out: &mut Cleaned, src: &MappedText, r: Range<usize>, policy: LinkPolicy) {
+        let mut link = Link::parse(&src.text[r.clone()]);
+        link.span = src.span(r.clone());
+        link.url = self.scrub(out, &link.url, |v| src.span(r.start + v.start..r.start + v.end));
+
+        let reference = self.references.as_ref().map(|_| link_reference(&link));
+        let token = match (&reference, policy) {
+            (Some((kind, _)), _) if !matches!(kind, ReferenceKind::Url(_)) => Token::Reference(*kind),
+            (_, LinkPolicy::Domain) if !link.domain.is_empty() => Token::Url(link.domain.clone()),
+            (_, LinkPolicy::Category) => Token::Url(link.category.name().to_owned()),
+            _ => Token::Link,
+        };
+        out.stats.dropped.url += 1;
+        out.tokens.push(SpannedToken {
+            token,
+            span: link.span.clone(),
+            lang: None,
+        });
+
+        if let Some((kind, target)) = reference {
+            self.push_reference(out, kind, target, link.span.clone());
+        }
+        if self.config.keep_links {
+            link.position = out.tokens.len() - 1;
+            out.links.push(link);
+        }
+    }
+
+    /// Records a reference whose token was just pushed.
+    fn push_reference(&self, out: &mut Cleaned, kind: ReferenceKind, target: String, span: Range<usize>) {
+        out.references.push(Reference {
+            kind,
+            target,
+            span,
+            position: out.tokens.len() - 1,
+        });
+    }
+
+    fn push_redacted(&self, out: &mut Cleaned, kind: PiiKind, src: &MappedText, r: Range<usize>) {
+        let Some(redactor) = &self.redactor else {
+            return;
+        };
+        let text = &src.text[r.clone()];
+        let replacement = redactor.replacement(kind, text, &out.redactions);
+        let span = src.span(r);
+        self.push_token(out, Token::Redacted(replacement.clone()), span.clone());
+        record_redaction(out, Redaction {
+            kind,
+            text: text.to_owned(),
+            replacement,
+            span,
+        });
+    }
+
+    /// `text` with its secrets and personal data replaced, for the lists kept
+    /// aside. Each is recorded with the input range `within` maps its range to.
+    fn scrub(&self, out: &mut Cleaned, text: &str, within: impl Fn(Range<usize>) -> Range<usize>) -> String {
+        let mut found = vec![];
+        if let Some(secrets) = &self.secrets {
+            found.extend(secrets.find(text).into_iter().map(|(r, kind)| (r, Replaced::Secret(kind))));
+        }
+        if let Some(redactor) = &self.redactor {
+            found.extend(redactor.find(text).into_iter().map(|(r, kind)| (r, Replaced::Pii(kind))));
+        }
+        found.sort_by_key(|(r, _)| r.start);
+
+        let mut scrubbed = String::new();
+        let mut last = 0;
+        for (r, found) in found {
+            if r.start < last {
+                continue;
+            }
+            scrubbed.push_str(&text[last..r.start]);
+            let span = within(r.clone());
+            match (found, &self.redactor) {
+                (Replaced::Secret(kind), _) => {
+                    scrubbed.push_str(&kind.placeholder());
+                    let secret = Secret { kind, span };
+                    if !out.secrets.contains(&secret) {
+                        out.secrets.push(secret);
+                    }
+                }
+                (Replaced::Pii(kind), Some(redactor)) => {
+                    let value = &text[r.clone()];
+                    let replacement = redactor.replacement(kind, value, &out.redactions);
+                    scrubbed.push_str(&replacement);
+                    record_redaction(out, Redaction {
+                        kind,
+                        text: value.to_owned(),
+                        replacement,
+                        span,
+                    });
+                }
+                // only secrets and personal data are looked for
+                _ => {}
+            }
+            last = r.end;
+        }
+        scrubbed.push_str(&text[last..]);
+
+        scrubbed
+    }
+
+    fn push_entity(&self, out: &mut Cleaned, entity: Entity, src: &MappedText, r: Range<usize>) {
+        let span = src.span(r.clone());
+        self.push_token(out, Token::Entity(entity), span.clone());
+        if self.config.entities.keep_values {
+            let text = self.scrub(out, &src.text[r.clone()], |v| src.span(r.start + v.start..r.start + v.end));
+            out.entities.push(EntityValue {
+                entity,
+                text,
+                span,
+                position: out.tokens.len() - 1,
+            });
+        }
+    }
+
+    fn tokenize_words(&self, src: &MappedText, range: Range<usize>, out: &mut Cleaned) {
+        let mut last = String::new();
+        let mut last_start = range.start;
+        let mut lastsplchar = ' ';
+
+        let mut escaped = false;
+
+        let flush = |out: &mut Cleaned, last: &mut String, span: Range<usize>| {
+            if !last.is_empty() {
+                self.push_word(out, last.trim(), src.span(span));
+                last.clear();
+            }
+        };
+
+        for (i, c) in src.text[range.clone()].char_indices() {
+            let i = range.start + i;
+            let char_span = i..i + c.len_utf8();
+
+            // handelling consecutive punctuations
+            if c.is_ascii_punctuation() && c == lastsplchar {
+                continue;
+            }
+            lastsplchar = c;
+
+            if c.is_whitespace() {
+                if !last.is_empty() {
+                    flush(out, &mut last, last_start..i);
+                    if c != ' ' && !src.collapse_ws {
+                        self.push_token(out, Token::Separator, src.span(char_span));
+                    }
+                }
+
+                continue;
+            }
+
+            if c == '\\' {
+                escaped = true;
+                continue;
+            }
+
+            if escaped {
+                escaped = false;
+                if c == 'n' || c == 't' || c == 'r' {
+                    flush(out, &mut last, last_start..i - 1);
+                    continue;
+                }
+            }
+
+            if self.config.split_chars.contains(&c)
+                || (self.config.url_split_chars.contains(&c) && !last.starts_with("http"))
+            {
+                flush(out, &mut last, last_start..i);
+                self.push_token(out, Token::Punct(c), src.span(char_span));
+                continue;
+            }
+
+            if last.is_empty() {
+                last_start = i;
+            }
+            last.push(c);
+        }
+
+        flush(out, &mut last, last_start..range.end);
+    }
+
+    /// Pushes a token, skipping exact repeats and separators right after punctuation.
+    fn push_token(&self, out: &mut Cleaned, token: Token, span: Range<usize>) {
+        self.push_tagged(out, token, span, None)
+    }
+
+    fn push_tagged(&self, out: &mut Cleaned, token: Token, span: Range<usize>, lang: Option<Lang>) {
+        if let Some(p) = out.tokens.last() {
+            if p.token == token {
+                if matches!(token, Token::Word(_)) {
+                    out.stats.dropped.duplicate += 1;
+                }
+                return;
+            }
+
+            if token == Token::Separator
+                && matches!(p.token, Token::Punct(c) if is_special_punctuation(&c.to_string()))
+            {
+                return;
+            }
+        }
+
+        out.tokens.push(SpannedToken { token, span, lang });
+    }
+
+    fn push_word(&self, out: &mut Cleaned, txt: &str, span: Range<usize>) {
+        let txt = txt.trim();
+        let charcount = txt.chars().count();
+
+        let language = &self.config.language;
+        let mut lang = None;
+        if language.mode != LanguageMode::Off && language.scope == LanguageScope::Token && charcount > 1 {
+            if let Some(info) = detect(txt) {
+                if language.mode == LanguageMode::Filter {
+                    if !language.allows_script(info.script()) {
+                        out.stats.dropped.disallowed_script += 1;
+                        return;
+                    }
+
+                    if !language.allows_lang(info.lang(), info.confidence()) {
+                        out.stats.dropped.disallowed_lang += 1;
+                        return;
+                    }
+                }
+
+                lang = Some(info.lang());
+            }
+        }
+
+        if charcount > self.config.max_token_chars {
+            out.stats.dropped.over_length += 1;
+            self.push_token(out, Token::LongText, span);
+        } else {
+            self.push_tagged(out, Token::Word(txt.to_owned()), span, lang);
+        }
+    }
+}
+
+/// Tokens produced by a [`Cleaner`] together with their statistics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cleaned {
+    pub tokens: Vec<SpannedToken>,
+    pub stats: CleanStats,
+    /// Language of the document as a whole, unless detection is off.
+    pub language: Option<DetectedLanguage>,
+    /// Code kept aside under [`CodeBlockPolicy::Verbatim`] or
+    /// [`CleanerConfig::extract_code`].
+    pub code: Vec<CodeSnippet>,
+    /// Text recognized as code by [`detect_code`](crate::detect_code) or
+    /// [`detect_code_blocks`](crate::detect_code_blocks), with spans into the input.
+    pub code_regions: Vec<CodeRegion>,
+    /// Stack traces kept under [`TracePolicy::Structured`].
+    pub traces: Vec<StackTrace>,
+    /// Values replaced with entity placeholders, kept under
+    /// [`EntityConfig::keep_values`](crate::EntityConfig::keep_values).
+    pub entities: Vec<EntityValue>,
+    /// Personal data removed under [`CleanerConfig::redaction`].
+    pub redactions: Vec<Redaction>,
+    /// Credentials scrubbed under [`CleanerConfig::secrets`].
+    pub secrets: Vec<Secret>,
+    /// URLs kept under [`CleanerConfig::keep_links`].
+    pub links: Vec<Link>,
+    /// Cross-references collected under [`CleanerConfig::references`].
+    pub references: References,
+}
+
+impl Cleaned {
+    /// Splits the tokens at heading labels, see [`TagPolicy::Label`]. Tokens
+    /// before the first heading form a section without one.
+    pub fn sections(&self) -> Vec<Section<'_>> {
+        let mut sections = vec![];
+        let mut current = Section {
+            heading: None,
+            body: &[],
+        };
+        let mut start = 0;
+        for (i, t) in self.tokens.iter().enumerate() {
+            if let Token::Label(MarkdownElement::Heading, label) = &t.token {
+                current.body = &self.tokens[start..i];
+                if current.heading.is_some() || !current.body.is_empty() {
+                    sections.push(current);
+                }
+                current = Section {
+                    heading: Some(label),
+                    body: &[],
+                };
+                start = i + 1;
+            }
+        }
+        current.body = &self.tokens[start..];
+        if current.heading.is_some() || !current.body.is_empty() {
+            sections.push(current);
+        }
+
+        sections
+    }
+
+    /// Token positions recorded in the side lists.
+    fn positions_mut(&mut self) -> impl Iterator<Item = &mut usize> {
+        let code = self.code.iter_mut().map(|s| &mut s.position);
+        let entities = self.entities.iter_mut().map(|v| &mut v.position);
+        let links = self.links.iter_mut().map(|l| &mut l.position);
+        let references = self.references.iter_mut().map(|r| &mut r.position);
+
+        code.chain(entities).chain(links).chain(references)
+    }
+
+    /// Drops the tokens whose `keep` entry is false along with the code,
+    /// entities, links and references recorded at them, and moves the
+    /// positions of the rest along.
+    pub(crate) fn retain_tokens(&mut self, keep: &[bool]) {
+        let kept = |position: usize| keep.get(position).copied().unwrap_or(true);
+        self.code.retain(|s| kept(s.position));
+        self.entities.retain(|v| kept(v.position));
+        self.links.retain(|l| kept(l.position));
+        self.references.retain(|r| kept(r.position));
+
+        let mut before = Vec::with_capacity(keep.len() + 1);
+        let mut kept = 0;
+        for &k in keep {
+            before.push(kept);
+            kept += k as usize;
+        }
+        before.push(kept);
+
+        for position in self.positions_mut() {
+            *position = before[(*position).min(keep.len())];
+        }
+
+        let mut keep = keep.iter();
+        self.tokens.retain(|_| *keep.next().unwrap_or(&true));
+    }
+}
+
+/// Records `redaction` unless the same value at the same place already is,
+/// as when a code block's first line is summarized and the block extracted.
+fn record_redaction(out: &mut Cleaned, redaction: Redaction) {
+    if !out.redactions.iter().any(|r| r.span == redaction.span && r.kind == redaction.kind) {
+        out.redactions.push(redaction);
+    }
+}
+
+fn code_snippet(origin: CodeOrigin, lang: Option<String>, text: String, span: Range<usize>) -> CodeSnippet {
+    CodeSnippet {
+        origin,
+        lang,
+        text,
+        span,
+        position: 0,
+    }
+}
+
+/// Code or a stack trace found in the lines of a markdown paragraph.
+enum Inset {
+    Code(CodeRegion),
+    Trace(StackTrace),
+}
+
+impl Inset {
+    fn span(&self) -> &Range<usize> {
+        match self {
+            Self::Code(region) => &region.span,
+            Self::Trace(trace) => &trace.span,
+        }
+    }
+
+    fn span_mut(&mut self) -> &mut Range<usize> {
+        match self {
+            Self::Code(region) => &mut region.span,
+            Self::Trace(trace) => &mut trace.span,
+        }
+    }
+}
+
+/// A value taken out of the text before it is split into words.
+enum Replaced {
+    Link,
+    Entity(Entity),
+    Pii(PiiKind),
+    Reference(ReferenceKind, String),
+    Secret(SecretKind),
+}
+
+/// A heading and the tokens up to the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section<'a> {
+    pub heading: Option<&'a str>,
+    pub body: &'a [SpannedToken],
+}
+
+fn count_dropped(dropped: &mut DropStats, element: Option<MarkdownElement>, words: usize) {
+    match element {
+        Some(MarkdownElement::Heading) => dropped.heading += words,
+        _ => dropped.markup += words,
+    }
+}
+
+fn code_origin(kind: &CodeBlockKind) -> CodeOrigin {
+    match kind {
+        CodeBlockKind::Fenced(_) => CodeOrigin::Fenced,
+        CodeBlockKind::Indented => CodeOrigin::Indented,
+    }
+}
@@ -0,0 +1,278 @@
+use std::ops::Range;
+
+/// Where a code snippet was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeOrigin {
+    /// A markdown code span.
+    Inline,
+    /// A fenced markdown code block.
+    Fenced,
+    /// An indented markdown code block.
+    Indented,
+    /// A `<pre>` or `<code>` element.
+    Html,
+    /// Text recognized as code by [`detect_code`] or [`detect_code_blocks`].
+    Detected,
+}
+
+/// Code taken out of the prose stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSnippet {
+    pub origin: CodeOrigin,
+    /// Language declared on the fence or element class, if any.
+    pub lang: Option<String>,
+    pub text: String,
+    /// Byte range of the input the snippet was taken from.
+    pub span: Range<usize>,
+    /// Index into the cleaned tokens where the snippet used to be.
+    pub position: usize,
+}
+
+/// A stretch of text that looks like code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeRegion {
+    /// Byte range of the text the region covers.
+    pub span: Range<usize>,
+    /// How code-like the region is, from 0 to 1.
+    pub confidence: f64,
+}
+
+// regions scoring below this are left as prose
+const CODE_THRESHOLD: f64 = 0.5;
+
+// a brace left open for longer than this is not treated as the start of code
+const MAX_REGION_BYTES: usize = 4096;
+
+// keywords that rarely show up in English prose
+const KEYWORDS: &[&str] = &[
+    "fn", "func", "function", "def", "var", "const", "elif", "impl", "struct", "enum", "nil", "null", "void",
+    "int", "bool", "return", "import", "lambda", "namespace", "typedef", "println", "printf", "console",
+    "echo", "sudo", "npm", "pip", "cargo",
+];
+
+// only counted in upper case, where they are unlikely to be prose
+const SQL_KEYWORDS: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "INSERT", "INTO", "UPDATE", "DELETE", "JOIN", "CREATE", "TABLE", "VALUES",
+];
+
+// prompts that start a shell or REPL session, whose output follows
+const PROMPTS: &[&str] = &["$ ", ">>> ", "PS> "];
+
+// a single line inside a prose paragraph has to look more like code than a
+// whole paragraph does
+const LINE_THRESHOLD: f64 = 0.7;
+
+/// Finds unfenced code in plain text: whole paragraphs that score as code, and
+/// runs of code lines or shell sessions inside prose paragraphs.
+pub fn detect_code_blocks(text: &str) -> Vec<CodeRegion> {
+    let mut regions = vec![];
+    for par in paragraphs(text) {
+        let confidence = block_score(&text[par.clone()]);
+        if confidence >= CODE_THRESHOLD {
+            regions.push(CodeRegion { span: par, confidence });
+            continue;
+        }
+
+        let mut run: Option<(Range<usize>, usize)> = None;
+        let mut session = false;
+        let mut close = |run: &mut Option<(Range<usize>, usize)>, session: bool| {
+            if let Some((span, lines)) = run.take() {
+                let confidence = if session { 0.9 } else { block_score(&text[span.clone()]) };
+                if session || (lines > 1 && confidence >= CODE_THRESHOLD) || confidence >= LINE_THRESHOLD {
+                    regions.push(CodeRegion { span, confidence });
+                }
+            }
+        };
+
+        let mut start = par.start;
+        for line in text[par.clone()].split_inclusive('\n') {
+            let span = start..start + line.trim_end().len();
+            start += line.len();
+
+            let prompt = PROMPTS.iter().any(|p| line.trim_start().starts_with(p));
+            if prompt && !session {
+                close(&mut run, session);
+                session = true;
+            }
+
+            if session || prompt || block_score(line) >= CODE_THRESHOLD {
+                match &mut run {
+                    Some((r, lines)) => {
+                        r.end = span.end;
+                        *lines += 1;
+                    }
+                    None => run = Some((span, 1)),
+                }
+            } else {
+                close(&mut run, session);
+            }
+        }
+        close(&mut run, session);
+    }
+
+    regions
+}
+
+/// Scores a paragraph or line: [`code_score`] plus bonuses for indented or
+/// prompted lines and for balanced brackets.
+fn block_score(text: &str) -> f64 {
+    let lines = text.lines().filter(|l| !l.trim().is_empty()).collect::<Vec<_>>();
+    let indented = lines
+        .iter()
+        .filter(|l| l.starts_with([' ', '\t']) || PROMPTS.iter().any(|p| l.starts_with(p)))
+        .count();
+    let indent_ratio = indented as f64 / lines.len().max(1) as f64;
+
+    let count = |c: char| text.chars().filter(|x| *x == c).count();
+    let pairs = [('(', ')'), ('[', ']'), ('{', '}')];
+    let has_brackets = pairs.iter().any(|(open, _)| count(*open) > 0);
+    let balanced = has_brackets && pairs.iter().all(|(open, close)| count(*open) == count(*close));
+
+    (code_score(text) + 0.1 * indent_ratio + if balanced { 0.1 } else { 0.0 }).min(1.0)
+}
+
+/// Byte ranges of the blank-line separated paragraphs of `text`, without their
+/// trailing whitespace.
+fn paragraphs(text: &str) -> Vec<Range<usize>> {
+    let mut pars = vec![];
+    let mut start = None;
+    let mut end = 0;
+    let mut pos = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            if let Some(s) = start.take() {
+                pars.push(s..end);
+            }
+        } else {
+            start.get_or_insert(pos);
+            end = pos + line.trim_end().len();
+        }
+        pos += line.len();
+    }
+    if let Some(s) = start {
+        pars.push(s..end);
+    }
+
+    pars
+}
+
+/// Finds balanced `{...}` blocks, escaped `<code>` elements and PHP tags in
+/// `text` and keeps the ones that look like code. A block starts at the
+/// beginning of its line or sentence when that opens it with a call or signature.
+pub fn detect_code(text: &str) -> Vec<CodeRegion> {
+    let mut regions: Vec<CodeRegion> = vec![];
+    let mut push = |span: Range<usize>, confidence: f64| {
+        if confidence < CODE_THRESHOLD {
+            return;
+        }
+        match regions.last_mut() {
+            Some(last) if last.span.end >= span.start => {
+                last.span.end = last.span.end.max(span.end);
+                last.confidence = last.confidence.max(confidence);
+            }
+            _ => regions.push(CodeRegion { span, confidence }),
+        }
+    };
+
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let tagged = [("<code>", "</code>"), ("<?php", "?>")]
+            .iter()
+            .find(|(open, _)| rest.starts_with(open));
+        if let Some((open, close)) = tagged {
+            let end = rest[open.len()..]
+                .find(close)
+                .map_or(text.len(), |j| i + open.len() + j + close.len());
+            push(i..end, 1.0);
+            i = end;
+            continue;
+        }
+
+        if rest.starts_with('{') {
+            if let Some(end) = closing_brace(text, i) {
+                let line_start = [text[..i].rfind('\n').map(|n| n + 1), text[..i].rfind(". ").map(|n| n + 2)]
+                    .into_iter()
+                    .flatten()
+                    .max()
+                    .unwrap_or(0);
+                // a call's `(` before the brace starts the region at the name being called
+                let start = text[line_start..i].rfind('(').map_or(i, |p| {
+                    let head = &text[line_start..line_start + p];
+                    line_start + head.trim_end_matches(|c: char| c.is_alphanumeric() || "_.:".contains(c)).len()
+                });
+                if has_statement(&text[start..end]) {
+                    push(start..end, code_score(&text[start..end]));
+                }
+                i = end;
+                continue;
+            }
+        }
+
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+
+    regions
+}
+
+/// Whether a braced block holds more than a placeholder like `{name}`: some
+/// punctuation of a statement, call or key-value pair, or a keyword.
+fn has_statement(text: &str) -> bool {
+    text.contains([';', '=', ':', '(', '"'])
+        || text
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .any(|w| KEYWORDS.contains(&w.to_lowercase().as_str()) || SQL_KEYWORDS.contains(&w))
+}
+
+/// Finds the end of the block opened by the `{` at `start`.
+fn closing_brace(text: &str, start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (j, c) in text[start..].char_indices() {
+        if j > MAX_REGION_BYTES {
+            return None;
+        }
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + j + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Scores how code-like `text` is from its symbol density, the share of
+/// keywords among its words and how many of its lines end like statements.
+fn code_score(text: &str) -> f64 {
+    let visible = text.chars().filter(|c| !c.is_whitespace()).count();
+    if visible == 0 {
+        return 0.0;
+    }
+    let symbols = text.chars().filter(|c| "{}[]()<>;=:&|+*/\\$#\"".contains(*c)).count();
+
+    let words = text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+    let keywords = words
+        .iter()
+        .filter(|w| KEYWORDS.contains(&w.to_lowercase().as_str()) || SQL_KEYWORDS.contains(w))
+        .count();
+
+    let lines = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>();
+    let statements = lines
+        .iter()
+        .filter(|l| l.ends_with([';', '{']))
+        .count();
+
+    let density = (symbols as f64 / visible as f64 * 3.0).min(1.0);
+    let keyword_ratio = (keywords as f64 / words.len().max(1) as f64 * 3.0).min(1.0);
+    let statement_ratio = statements as f64 / lines.len().max(1) as f64;
+
+    0.5 * density + 0.4 * keyword_ratio + 0.1 * statement_ratio
+}
@@ -0,0 +1,377 @@
+use std::collections::HashSet;
+
+use pulldown_cmark::{LinkType, Tag};
+use whatlang::{Lang, Script};
+
+use crate::{Cleaner, Entity, MarkdownElement, PiiKind, TemplateConfig};
+
+/// Every policy knob used while cleaning. `Default` reproduces the behaviour of
+/// the free `clean_text*` functions.
+#[derive(Debug, Clone)]
+pub struct CleanerConfig {
+    pub language: LanguageConfig,
+    pub markdown: MarkdownPolicy,
+    /// Issue-template boilerplate removed from markdown before parsing.
+    pub templates: TemplateConfig,
+    pub code_blocks: CodeBlockPolicy,
+    /// Find unfenced code with [`detect_code_blocks`](crate::detect_code_blocks)
+    /// and treat it like a code block.
+    pub detect_code: bool,
+    pub traces: TracePolicy,
+    /// Frames kept in a stack trace summary.
+    pub trace_frames: usize,
+    /// Replace timestamps, UUIDs, pointers and numbers in log lines, and the
+    /// lines around them, with typed placeholders, so repeated failures clean
+    /// to the same tokens. Code kept aside is normalized the same way.
+    pub normalize_logs: bool,
+    pub entities: EntityConfig,
+    pub redaction: RedactionConfig,
+    pub secrets: SecretConfig,
+    pub links: LinkPolicy,
+    /// Record every URL in [`Cleaned::links`](crate::Cleaned::links), with
+    /// secrets in it scrubbed.
+    pub keep_links: bool,
+    /// Replace issue and pull request references, mentions and commit SHAs
+    /// with placeholders, and collect them along with every URL into
+    /// [`Cleaned::references`](crate::Cleaned::references). Mentions redacted as
+    /// personal data are not collected.
+    pub references: bool,
+    /// Collect every code span, block and element into [`Cleaned::code`](crate::Cleaned::code).
+    pub extract_code: bool,
+    /// Keep the text of HTML comments instead of dropping it.
+    pub keep_html_comments: bool,
+    /// Tokens longer than this many chars are replaced with `long_text_placeholder`.
+    pub max_token_chars: usize,
+    pub link_placeholder: String,
+    pub code_placeholder: String,
+    pub long_text_placeholder: String,
+    /// Replacement for code regions found in HTML input.
+    pub code_section_placeholder: String,
+    pub image_placeholder: String,
+    pub separator: String,
+    /// Characters that end the current token and are emitted on their own.
+    pub split_chars: Vec<char>,
+    /// Like `split_chars`, but ignored while the current token starts with
+    /// `http`. URLs the link recognizer finds are never split.
+    pub url_split_chars: Vec<char>,
+}
+
+impl Default for CleanerConfig {
+    fn default() -> Self {
+        Self {
+            language: LanguageConfig::default(),
+            markdown: MarkdownPolicy::default(),
+            templates: TemplateConfig::default(),
+            code_blocks: CodeBlockPolicy::Placeholder,
+            detect_code: false,
+            traces: TracePolicy::Keep,
+            trace_frames: 3,
+            normalize_logs: false,
+            entities: EntityConfig::default(),
+            redaction: RedactionConfig::default(),
+            secrets: SecretConfig::default(),
+            links: LinkPolicy::Placeholder,
+            keep_links: false,
+            references: false,
+            extract_code: false,
+            keep_html_comments: false,
+            max_token_chars: 32,
+            link_placeholder: "link".to_owned(),
+            code_placeholder: "code".to_owned(),
+            long_text_placeholder: "long-text".to_owned(),
+            code_section_placeholder: "Section contained code.".to_owned(),
+            image_placeholder: "<IMAGE>".to_owned(),
+            separator: "[SEP]".to_owned(),
+            split_chars: vec![
+                '!', '?', ',', ';', '(', ')', '<', '>', '$', '&', '\'', '"', '[', ']',
+            ],
+            url_split_chars: vec![':', '.'],
+        }
+    }
+}
+
+impl CleanerConfig {
+    pub fn build(self) -> Cleaner {
+        Cleaner::new(self)
+    }
+}
+
+/// What happens to a markdown element and its content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagPolicy {
+    Ignore,
+    Keep,
+    /// Replace the whole element with one placeholder.
+    Replace,
+    /// Keep the content after a marker token naming the element.
+    Section,
+    /// Replace the element with one token holding its cleaned text. Headings
+    /// kept this way split [`Cleaned::sections`](crate::Cleaned::sections).
+    Label,
+}
+
+/// Per-element [`TagPolicy`] used by `clean_text_with_markdown`.
+#[derive(Debug, Clone)]
+pub struct MarkdownPolicy {
+    pub heading: TagPolicy,
+    pub block_quote: TagPolicy,
+    pub list: TagPolicy,
+    pub table: TagPolicy,
+    pub emphasis: TagPolicy,
+    pub link: TagPolicy,
+    pub image: TagPolicy,
+    /// What a link kept under [`TagPolicy::Keep`] turns into.
+    pub link_anchor: AnchorPolicy,
+    /// What an image kept under [`TagPolicy::Keep`] turns into.
+    pub image_anchor: AnchorPolicy,
+}
+
+impl Default for MarkdownPolicy {
+    fn default() -> Self {
+        Self {
+            heading: TagPolicy::Ignore,
+            block_quote: TagPolicy::Keep,
+            list: TagPolicy::Keep,
+            table: TagPolicy::Keep,
+            emphasis: TagPolicy::Keep,
+            link: TagPolicy::Keep,
+            image: TagPolicy::Keep,
+            link_anchor: AnchorPolicy::Text,
+            image_anchor: AnchorPolicy::Text,
+        }
+    }
+}
+
+impl MarkdownPolicy {
+    pub fn policy(&self, element: MarkdownElement) -> TagPolicy {
+        match element {
+            MarkdownElement::Heading => self.heading,
+            MarkdownElement::BlockQuote => self.block_quote,
+            MarkdownElement::List => self.list,
+            MarkdownElement::Table => self.table,
+            MarkdownElement::Emphasis => self.emphasis,
+            MarkdownElement::Link => self.link,
+            MarkdownElement::Image => self.image,
+        }
+    }
+
+    /// The anchor policy for a link or image tag. An autolink's text is its
+    /// destination, so it is only ever kept as a link or dropped.
+    pub(crate) fn anchor(&self, tag: &Tag) -> Option<AnchorPolicy> {
+        match tag {
+            Tag::Link(LinkType::Autolink, ..) if self.link_anchor != AnchorPolicy::Drop => {
+                Some(AnchorPolicy::Text)
+            }
+            Tag::Link(..) => Some(self.link_anchor),
+            Tag::Image(..) => Some(self.image_anchor),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to the text and destination of a markdown link or image.
+/// Reference-style links are resolved against their definitions first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnchorPolicy {
+    /// Keep the anchor or alt text, dropping the destination.
+    Text,
+    /// Replace the element with a link token for its destination, rendered
+    /// under [`CleanerConfig::links`].
+    Link,
+    /// Keep the text, followed by `<URL:domain>` for absolute destinations.
+    TextAndDomain,
+    /// Keep the text after the link or image placeholder, as in `<IMAGE> a
+    /// screenshot of the error`.
+    Marker,
+    /// Drop the element and its text.
+    Drop,
+}
+
+/// How URLs are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkPolicy {
+    /// The link placeholder.
+    Placeholder,
+    /// The domain, as `<URL:github.com>`.
+    Domain,
+    /// The kind of site, as `<URL:issue-tracker>`; see [`LinkCategory`](crate::LinkCategory).
+    Category,
+}
+
+/// What happens to fenced and indented markdown code blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeBlockPolicy {
+    Drop,
+    /// Replace the block with one placeholder carrying the fence language.
+    Placeholder,
+    /// Like `Placeholder`, also keeping the block in [`Cleaned::code`](crate::Cleaned::code).
+    Verbatim,
+    /// Like `Placeholder`, followed by the first line of the block cleaned as prose.
+    Summarize,
+}
+
+/// What happens to stack traces in plain text and code blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracePolicy {
+    /// Leave traces to the other rules.
+    Keep,
+    /// Replace each trace with its language, message and top frames.
+    Summarize,
+    /// Like `Summarize`, also keeping the trace in [`Cleaned::traces`](crate::Cleaned::traces).
+    Structured,
+}
+
+/// Which technical values, such as emails or commit hashes, are replaced with
+/// a typed placeholder before the text is split on punctuation.
+#[derive(Debug, Clone, Default)]
+pub struct EntityConfig {
+    /// Entity types to replace; empty replaces none.
+    pub enabled: HashSet<Entity>,
+    /// Keep each replaced value in [`Cleaned::entities`](crate::Cleaned::entities).
+    pub keep_values: bool,
+}
+
+impl EntityConfig {
+    /// Replaces every entity type.
+    pub fn all() -> Self {
+        Self {
+            enabled: HashSet::from([
+                Entity::Timestamp,
+                Entity::Uuid,
+                Entity::Hex,
+                Entity::Number,
+                Entity::Id,
+                Entity::Email,
+                Entity::Ip,
+                Entity::Sha,
+                Entity::Semver,
+                Entity::Path,
+            ]),
+            keep_values: false,
+        }
+    }
+}
+
+/// Which personal data is redacted, and what replaces it. Redacted values are
+/// listed in [`Cleaned::redactions`](crate::Cleaned::redactions), and replaced
+/// in the code, links, entity values and trace messages kept aside too.
+#[derive(Debug, Clone, Default)]
+pub struct RedactionConfig {
+    /// Kinds to redact; empty redacts nothing.
+    pub enabled: HashSet<PiiKind>,
+    pub mode: RedactionMode,
+}
+
+impl RedactionConfig {
+    /// Redacts every kind with `mode`.
+    pub fn all(mode: RedactionMode) -> Self {
+        Self {
+            enabled: HashSet::from([
+                PiiKind::Email,
+                PiiKind::Handle,
+                PiiKind::Phone,
+                PiiKind::Ip,
+                PiiKind::UserPath,
+            ]),
+            mode,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RedactionMode {
+    /// One placeholder per kind, like `<EMAIL>`.
+    #[default]
+    Placeholder,
+    /// Distinct values numbered in order of appearance within each document,
+    /// like `<USER_1>`.
+    DocumentPseudonym,
+    /// Values named after an HMAC keyed with the salt, so the same value gets
+    /// the same pseudonym in every document cleaned with the same salt.
+    CorpusPseudonym { salt: String },
+}
+
+/// Credential scrubbing. Secrets are replaced with `<SECRET:kind>` and listed
+/// in [`Cleaned::secrets`](crate::Cleaned::secrets), also inside extracted code.
+#[derive(Debug, Clone)]
+pub struct SecretConfig {
+    pub enabled: bool,
+    /// Bits of entropy per char above which a long mixed-case token counts as
+    /// a secret; `None` only looks for known formats.
+    pub min_entropy: Option<f64>,
+}
+
+impl Default for SecretConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_entropy: Some(4.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageMode {
+    /// Drop words outside the allowlists.
+    Filter,
+    /// Keep every word, only recording its detected language.
+    Tag,
+    /// Skip language detection.
+    Off,
+}
+
+/// How much text a single language decision covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageScope {
+    /// Every word on its own. Slow, and unreliable for short words.
+    Token,
+    Sentence,
+    /// Blocks of text separated by blank lines.
+    Paragraph,
+    Document,
+}
+
+/// Which languages and scripts survive cleaning.
+#[derive(Debug, Clone)]
+pub struct LanguageConfig {
+    pub mode: LanguageMode,
+    pub scope: LanguageScope,
+    /// Languages to keep; empty keeps every language.
+    pub langs: HashSet<Lang>,
+    /// Scripts to keep; empty keeps every script.
+    pub scripts: HashSet<Script>,
+    /// A language outside `langs` only causes a drop when detected with more
+    /// than this confidence.
+    pub min_confidence: f64,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            mode: LanguageMode::Filter,
+            scope: LanguageScope::Document,
+            langs: HashSet::from([Lang::Eng]),
+            scripts: HashSet::from([Script::Latin]),
+            min_confidence: 0.6,
+        }
+    }
+}
+
+impl LanguageConfig {
+    /// Keeps everything detected with one of `langs`, in any script.
+    pub fn allow(langs: impl IntoIterator<Item = Lang>) -> Self {
+        Self {
+            langs: langs.into_iter().collect(),
+            scripts: HashSet::new(),
+            ..Default::default()
+        }
+    }
+
+    pub fn allows_script(&self, script: Script) -> bool {
+        self.scripts.is_empty() || self.scripts.contains(&script)
+    }
+
+    pub fn allows_lang(&self, lang: Lang, confidence: f64) -> bool {
+        self.langs.is_empty() || self.langs.contains(&lang) || confidence <= self.min_confidence
+    }
+}
@@ -0,0 +1,139 @@
+use std::{collections::HashSet, ops::Range};
+
+use regex::Regex;
+
+use crate::redact::is_ipv4;
+
+/// A kind of volatile or technical value replaced with a typed placeholder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Entity {
+    Timestamp,
+    Uuid,
+    Hex,
+    Number,
+    /// A generated name suffix, like the `x7b2z` of `e2e-tests-kubectl-x7b2z`.
+    Id,
+    Email,
+    /// An IPv4 or IPv6 address, with an optional port.
+    Ip,
+    /// A commit hash, abbreviated or full.
+    Sha,
+    /// A semantic version such as `0.3.0-beta.2`.
+    Semver,
+    /// A file path, with an optional `:line` suffix.
+    Path,
+}
+
+impl Entity {
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            Self::Timestamp => "<TIMESTAMP>",
+            Self::Uuid => "<UUID>",
+            Self::Hex => "<HEX>",
+            Self::Number => "<NUM>",
+            Self::Id => "<ID>",
+            Self::Email => "<EMAIL>",
+            Self::Ip => "<IP>",
+            Self::Sha => "<SHA>",
+            Self::Semver => "<SEMVER>",
+            Self::Path => "<PATH>",
+        }
+    }
+}
+
+/// A value replaced with an entity placeholder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityValue {
+    pub entity: Entity,
+    pub text: String,
+    /// Byte range of the input the value was taken from.
+    pub span: Range<usize>,
+    /// Index of the placeholder in the cleaned tokens.
+    pub position: usize,
+}
+
+// tried in this order at each position, so more specific shapes come first
+const PATTERNS: &[(Entity, &str)] = &[
+    (
+        Entity::Timestamp,
+        r"\b\d{4}-\d{2}-\d{2}[t ]\d{1,2}:\d{2}(?::\d{2})?(?:[.,]\d+)?(?:z|[+-]\d{2}:?\d{2})?",
+    ),
+    (
+        Entity::Uuid,
+        r"\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b",
+    ),
+    (Entity::Email, r"\b[\w.+-]+@[\w-]+(?:\.[\w-]+)+\b"),
+    (
+        Entity::Path,
+        r"(?:\b[a-z]:|~|\.{1,2})?(?:[/\\][\w.@+-]+){2,}(?::\d+)*|\b[\w.-]+(?:/[\w.-]+)+\.[a-z]\w*(?::\d+)*",
+    ),
+    (
+        Entity::Ip,
+        r"\b(?:\d{1,3}\.){3}\d{1,3}(?::\d+)?\b|\b(?:[0-9a-f]{1,4}:){7}[0-9a-f]{1,4}\b|\b(?:[0-9a-f]{1,4}:){2,}(?::[0-9a-f]{1,4})+\b|\b(?:[0-9a-f]{1,4}:)+(?::[0-9a-f]{1,4}){2,}\b",
+    ),
+    (
+        Entity::Semver,
+        r"\bv?\d+\.\d+\.\d+(?:-[0-9a-z.]+)?(?:\+[0-9a-z.]+)?\b",
+    ),
+    (Entity::Hex, r"\b0x[0-9a-f]+\b"),
+    (Entity::Number, r"\b\d+(?:[.,]\d+)*\b"),
+    (Entity::Sha, r"\b[0-9a-f]{7,40}\b"),
+];
+
+/// Finds the enabled entity types in text.
+#[derive(Debug, Clone)]
+pub(crate) struct EntityRecognizer {
+    rgx: Regex,
+    kinds: Vec<Entity>,
+}
+
+impl EntityRecognizer {
+    /// Returns `None` when no entity type with a pattern is enabled.
+    pub(crate) fn new(enabled: &HashSet<Entity>) -> Option<Self> {
+        let (kinds, patterns): (Vec<_>, Vec<_>) = PATTERNS
+            .iter()
+            .filter(|(kind, _)| enabled.contains(kind))
+            .map(|(kind, p)| (*kind, format!("({p})")))
+            .unzip();
+        if kinds.is_empty() {
+            return None;
+        }
+
+        let rgx = Regex::new(&format!("(?i){}", patterns.join("|"))).unwrap();
+        Some(Self { rgx, kinds })
+    }
+
+    /// The entities in `text`, leaving out anything inside a URL.
+    pub(crate) fn find(&self, text: &str) -> Vec<(Range<usize>, Entity)> {
+        let mut found = vec![];
+        for c in self.rgx.captures_iter(text) {
+            let m = c.get(0).unwrap();
+            let Some(kind) = (0..self.kinds.len()).find(|i| c.get(i + 1).is_some()).map(|i| self.kinds[i]) else {
+                continue;
+            };
+
+            let word_start = text[..m.start()].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+            let word = &text[word_start..];
+            let word = &word[..word.find(char::is_whitespace).unwrap_or(word.len())];
+            if word.contains("://") || word.starts_with("www.") {
+                continue;
+            }
+
+            // a hash mixes digits and letters, anything else is a word or number
+            let s = m.as_str();
+            if kind == Entity::Sha
+                && !(s.chars().any(|c| c.is_ascii_digit()) && s.chars().any(|c| c.is_ascii_alphabetic()))
+            {
+                continue;
+            }
+
+            if kind == Entity::Ip && s.contains('.') && !is_ipv4(text, m.range()) {
+                continue;
+            }
+
+            found.push((m.range(), kind));
+        }
+
+        found
+    }
+}
@@ -0,0 +1,31 @@
+use std::{error::Error, fmt, io};
+
+/// Errors reported by the `try_clean*` functions.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JanitorError {
+    /// The HTML parser could not read its input.
+    Html(io::Error),
+}
+
+impl fmt::Display for JanitorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JanitorError::Html(e) => write!(f, "failed to parse html: {e}"),
+        }
+    }
+}
+
+impl Error for JanitorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JanitorError::Html(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for JanitorError {
+    fn from(e: io::Error) -> Self {
+        JanitorError::Html(e)
+    }
+}
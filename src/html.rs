@@ -0,0 +1,279 @@
+use std::{io::Cursor, ops::Range};
+
+use html5ever::{parse_document, tendril::TendrilSink, tree_builder::TreeSink, Attribute};
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+
+use crate::{source::MappedText, JanitorError};
+
+/// The text of an HTML document and the code elements left out of it.
+pub(crate) struct HtmlText {
+    pub(crate) text: MappedText,
+    pub(crate) code: Vec<HtmlCode>,
+    /// Words inside comments that were left out.
+    pub(crate) comment_words: usize,
+}
+
+/// A `<pre>` or `<code>` element.
+pub(crate) struct HtmlCode {
+    /// Offset into the extracted text where the element was.
+    pub(crate) at: usize,
+    pub(crate) text: String,
+    pub(crate) lang: Option<String>,
+    pub(crate) span: Range<usize>,
+}
+
+enum Piece {
+    Text(String),
+    Comment(String),
+    Code {
+        tag: String,
+        text: String,
+        lang: Option<String>,
+    },
+}
+
+impl HtmlText {
+    /// Shifts every source offset by `base`.
+    pub(crate) fn offset(&mut self, base: usize) {
+        self.text.offset(base);
+        for c in &mut self.code {
+            c.span = c.span.start + base..c.span.end + base;
+        }
+    }
+
+    /// Maps everything to `src`.
+    pub(crate) fn collapse(&mut self, src: Range<usize>) {
+        self.text.collapse(src.clone());
+        for c in &mut self.code {
+            c.span = src.clone();
+        }
+    }
+}
+
+/// Extracts the text of an HTML document, mapped back onto `s`. Comments are
+/// only part of the text when `keep_comments` is set.
+pub(crate) fn parse_html(s: &str, keep_comments: bool) -> Result<HtmlText, JanitorError> {
+    let mut cur = Cursor::new(s.as_bytes());
+    let mut dom = parse_document(RcDom::default(), Default::default())
+        .from_utf8()
+        .read_from(&mut cur)?;
+
+    let node = dom.get_document();
+    let mut pieces = vec![];
+    parse_node(&node, &mut pieces);
+
+    let mut mapped = MappedText::default();
+    mapped.collapse_ws = true;
+    let mut code = vec![];
+    let mut comment_words = 0;
+    let mut pos = 0;
+    let mut first = true;
+    for piece in pieces {
+        let (txt, in_comment) = match piece {
+            Piece::Text(txt) => (txt, false),
+            Piece::Comment(txt) if keep_comments => (txt, true),
+            Piece::Comment(txt) => {
+                comment_words += txt.split_whitespace().count();
+                continue;
+            }
+            Piece::Code { tag, text, lang } => {
+                let span = find_element(s, pos, &tag).unwrap_or(pos..pos);
+                pos = span.end;
+                code.push(HtmlCode {
+                    at: mapped.text.len(),
+                    text,
+                    lang,
+                    span,
+                });
+                continue;
+            }
+        };
+
+        if !first {
+            mapped.push_synthetic(" . ", pos..pos);
+        }
+        first = false;
+
+        if let Some(at) = find_text(s, pos, &txt, in_comment) {
+            mapped.push_exact(&txt, at);
+            pos = at + txt.len();
+            continue;
+        }
+
+        // entities and collapsed whitespace keep the node from matching as a
+        // whole, so place it word by word
+        for (j, word) in txt.split_whitespace().enumerate() {
+            if j > 0 {
+                mapped.push_synthetic(" ", pos..pos);
+            }
+            match find_text(s, pos, word, in_comment) {
+                Some(at) => {
+                    mapped.push_exact(word, at);
+                    pos = at + word.len();
+                }
+                None => mapped.push_synthetic(word, pos..pos),
+            }
+        }
+    }
+
+    Ok(HtmlText {
+        text: mapped,
+        code,
+        comment_words,
+    })
+}
+
+// elements that never have a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr",
+];
+
+// elements with content seen in issues; anything else, like the `<T>` of
+// `Option<T>`, is not taken as an open element
+const ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "blockquote", "body", "center", "cite", "code", "dd", "del", "details", "div", "dl", "dt", "em",
+    "figcaption", "figure", "font", "h1", "h2", "h3", "h4", "h5", "h6", "html", "i", "ins", "kbd", "li", "mark", "ol",
+    "p", "pre", "q", "s", "samp", "small", "span", "strike", "strong", "sub", "summary", "sup", "table", "tbody", "td",
+    "tfoot", "th", "thead", "tr", "tt", "u", "ul", "var",
+];
+
+/// Whether `s` opens a known element or a comment without closing it again.
+pub(crate) fn has_open_element(s: &str) -> bool {
+    let mut depth = 0usize;
+    let mut rest = s;
+    while let Some(i) = rest.find('<') {
+        rest = &rest[i + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            match comment.find("-->") {
+                Some(j) => rest = &comment[j + 3..],
+                None => return true,
+            }
+            continue;
+        }
+
+        let closing = rest.starts_with('/');
+        // tags are matched as written, so `Vec<B>` is no bold element
+        let name = rest
+            .trim_start_matches('/')
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+            .next()
+            .unwrap_or_default();
+        if !ELEMENTS.contains(&name) && !VOID_ELEMENTS.contains(&name) {
+            continue;
+        }
+
+        let Some(end) = rest.find('>') else {
+            return true;
+        };
+        let self_closing = rest[..end].ends_with('/');
+        rest = &rest[end + 1..];
+        if self_closing || VOID_ELEMENTS.contains(&name) {
+            continue;
+        }
+
+        if closing {
+            depth = depth.saturating_sub(1);
+        } else {
+            depth += 1;
+        }
+    }
+
+    depth > 0
+}
+
+/// Finds the element opened by the next `<tag` at or after `from`, up to its
+/// closing tag or the end of `s`.
+fn find_element(s: &str, from: usize, tag: &str) -> Option<Range<usize>> {
+    let start = from + s.get(from..)?.find(&format!("<{tag}"))?;
+    let close = format!("</{tag}>");
+    let end = s[start..]
+        .find(&close)
+        .map_or(s.len(), |i| start + i + close.len());
+
+    Some(start..end)
+}
+
+/// Finds `needle` in `s` at or after `from`, skipping matches inside tags other
+/// than a comment when looking for comment text.
+fn find_text(s: &str, from: usize, needle: &str, in_comment: bool) -> Option<usize> {
+    let mut from = from;
+    while let Some(i) = s.get(from..)?.find(needle) {
+        let at = from + i;
+        let before = &s[..at];
+        match (before.rfind('<'), before.rfind('>')) {
+            (Some(lt), gt) if gt.is_none_or(|gt| gt < lt) && !(in_comment && s[lt..].starts_with("<!--")) => {
+                from = at + needle.len().max(1);
+            }
+            _ => return Some(at),
+        }
+    }
+
+    None
+}
+
+// walks the tree with an explicit stack so deeply nested input can't overflow
+fn parse_node(node: &Handle, pieces: &mut Vec<Piece>) {
+    let mut stack = vec![node.clone()];
+    while let Some(node) = stack.pop() {
+        match node.data {
+            NodeData::Text { ref contents } => {
+                let txt = contents.borrow().trim().to_string();
+                if !txt.is_empty() {
+                    pieces.push(Piece::Text(txt));
+                }
+            }
+
+            NodeData::Comment { ref contents } => {
+                let txt = contents.trim().to_string();
+                if !txt.is_empty() {
+                    pieces.push(Piece::Comment(txt));
+                }
+            }
+
+            NodeData::Element {
+                ref name,
+                ref attrs,
+                ..
+            } if name.local.as_bytes() == "pre".as_bytes()
+                || name.local.as_bytes() == "code".as_bytes() =>
+            {
+                let mut text = String::new();
+                let mut lang = class_lang(&attrs.borrow());
+                let mut inner = vec![node.clone()];
+                while let Some(n) = inner.pop() {
+                    match n.data {
+                        NodeData::Text { ref contents } => text.push_str(&contents.borrow()),
+                        NodeData::Element { ref attrs, .. } if lang.is_none() => {
+                            lang = class_lang(&attrs.borrow());
+                        }
+                        _ => {}
+                    }
+                    inner.extend(n.children.borrow().iter().rev().cloned());
+                }
+
+                pieces.push(Piece::Code {
+                    tag: name.local.to_string(),
+                    text,
+                    lang,
+                });
+                continue;
+            }
+
+            // `<?xml ...?>` and friends carry no text
+            NodeData::ProcessingInstruction { .. } => continue,
+            _ => {}
+        }
+
+        stack.extend(node.children.borrow().iter().rev().cloned());
+    }
+}
+
+/// Reads the language from a `language-xxx` or `lang-xxx` class.
+fn class_lang(attrs: &[Attribute]) -> Option<String> {
+    let class = attrs.iter().find(|a| a.name.local.as_bytes() == b"class")?;
+    class.value.split_whitespace().find_map(|c| {
+        c.strip_prefix("language-")
+            .or_else(|| c.strip_prefix("lang-"))
+            .map(str::to_lowercase)
+    })
+}
@@ -0,0 +1,211 @@
+use std::ops::Range;
+
+use whatlang::{detect, Lang, Script};
+
+use crate::{Cleaned, LanguageConfig, LanguageMode, LanguageScope, SpannedToken, Token};
+
+/// A language guess for a stretch of text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectedLanguage {
+    pub lang: Lang,
+    pub script: Script,
+    pub confidence: f64,
+}
+
+impl DetectedLanguage {
+    pub fn detect(text: &str) -> Option<Self> {
+        detect(text).map(|info| Self {
+            lang: info.lang(),
+            script: info.script(),
+            confidence: info.confidence(),
+        })
+    }
+}
+
+/// Detects the language of the whole document and, for scopes coarser than a
+/// token, keeps or drops each paragraph or sentence as a unit.
+pub(crate) fn apply_language(config: &LanguageConfig, input: &str, out: &mut Cleaned) {
+    if config.mode == LanguageMode::Off {
+        return;
+    }
+
+    out.language = DetectedLanguage::detect(&words(&out.tokens));
+    if config.scope == LanguageScope::Token {
+        return;
+    }
+
+    let units = split_units(input, &out.tokens, |prev, gap| match config.scope {
+        LanguageScope::Sentence => is_paragraph_break(gap) || is_sentence_end(prev),
+        LanguageScope::Paragraph => is_paragraph_break(gap),
+        _ => false,
+    });
+    let mut keep = vec![true; out.tokens.len()];
+    for unit in units {
+        let detected = if unit.len() == out.tokens.len() {
+            out.language
+        } else {
+            DetectedLanguage::detect(&words(&out.tokens[unit.clone()]))
+        };
+        let Some(detected) = detected else {
+            continue;
+        };
+
+        let script_ok = config.allows_script(detected.script);
+        let lang_ok = config.allows_lang(detected.lang, detected.confidence);
+        if config.mode == LanguageMode::Tag || (script_ok && lang_ok) {
+            for t in &mut out.tokens[unit] {
+                if matches!(t.token, Token::Word(_)) {
+                    t.lang = Some(detected.lang);
+                }
+            }
+            continue;
+        }
+
+        let dropped = word_count(&out.tokens[unit.clone()]);
+        if !script_ok {
+            out.stats.dropped.disallowed_script += dropped;
+        } else {
+            out.stats.dropped.disallowed_lang += dropped;
+        }
+        keep[unit].fill(false);
+    }
+
+    out.retain_tokens(&keep);
+}
+
+fn words(tokens: &[SpannedToken]) -> String {
+    let mut text = String::new();
+    for t in tokens {
+        if let Token::Word(w) = &t.token {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(w);
+        }
+    }
+
+    text
+}
+
+/// A run of cleaned tokens written in one language.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageSegment {
+    /// Indices into the token list the segment was built from.
+    pub tokens: Range<usize>,
+    /// Byte range of the input the segment covers.
+    pub span: Range<usize>,
+    pub lang: Lang,
+    pub script: Script,
+    pub confidence: f64,
+}
+
+// units with fewer words are too short to detect reliably and join a neighbour
+const MIN_SEGMENT_WORDS: usize = 3;
+
+/// Splits cleaned tokens into language-homogeneous segments. Clean with
+/// [`LanguageMode::Tag`] or [`LanguageMode::Off`] so that no unit has been
+/// dropped before segmenting.
+pub fn language_segments(input: &str, tokens: &[SpannedToken]) -> Vec<LanguageSegment> {
+    let units = split_units(input, tokens, |prev, gap| {
+        is_paragraph_break(gap) || is_sentence_end(prev) || prev.token == Token::Separator
+    });
+
+    let mut segments: Vec<LanguageSegment> = vec![];
+    let mut pending: Option<Range<usize>> = None;
+    for unit in units {
+        let unit = match pending.take() {
+            Some(p) => p.start..unit.end,
+            None => unit,
+        };
+        let count = word_count(&tokens[unit.clone()]);
+        let detected = DetectedLanguage::detect(&words(&tokens[unit.clone()]));
+
+        let Some(detected) = detected.filter(|_| count >= MIN_SEGMENT_WORDS) else {
+            match segments.last_mut() {
+                Some(last) => extend(last, unit, tokens),
+                None => pending = Some(unit),
+            }
+            continue;
+        };
+
+        match segments.last_mut() {
+            Some(last) if last.lang == detected.lang && last.script == detected.script => {
+                let before = word_count(&tokens[last.tokens.clone()]);
+                last.confidence = (last.confidence * before as f64 + detected.confidence * count as f64)
+                    / (before + count) as f64;
+                extend(last, unit, tokens);
+            }
+            _ => segments.push(LanguageSegment {
+                span: span_of(&tokens[unit.clone()]),
+                tokens: unit,
+                lang: detected.lang,
+                script: detected.script,
+                confidence: detected.confidence,
+            }),
+        }
+    }
+
+    // nothing was long enough to detect on its own
+    if let Some(unit) = pending {
+        if let Some(detected) = DetectedLanguage::detect(&words(&tokens[unit.clone()])) {
+            segments.push(LanguageSegment {
+                span: span_of(&tokens[unit.clone()]),
+                tokens: unit,
+                lang: detected.lang,
+                script: detected.script,
+                confidence: detected.confidence,
+            });
+        }
+    }
+
+    segments
+}
+
+fn extend(segment: &mut LanguageSegment, unit: Range<usize>, tokens: &[SpannedToken]) {
+    let span = span_of(&tokens[unit.clone()]);
+    segment.tokens.end = unit.end;
+    segment.span.end = segment.span.end.max(span.end);
+}
+
+fn span_of(tokens: &[SpannedToken]) -> Range<usize> {
+    let start = tokens.iter().map(|t| t.span.start).min().unwrap_or_default();
+    let end = tokens.iter().map(|t| t.span.end).max().unwrap_or_default();
+
+    start..end
+}
+
+fn word_count(tokens: &[SpannedToken]) -> usize {
+    tokens.iter().filter(|t| matches!(t.token, Token::Word(_))).count()
+}
+
+fn is_paragraph_break(gap: &str) -> bool {
+    gap.matches('\n').count() >= 2
+}
+
+fn is_sentence_end(t: &SpannedToken) -> bool {
+    matches!(t.token, Token::Punct('.' | '!' | '?'))
+}
+
+/// Splits tokens wherever `is_break` holds for the previous token and the input
+/// between it and the next one.
+fn split_units(
+    input: &str,
+    tokens: &[SpannedToken],
+    is_break: impl Fn(&SpannedToken, &str) -> bool,
+) -> Vec<Range<usize>> {
+    let mut units = vec![];
+    let mut start = 0;
+    for i in 1..tokens.len() {
+        let gap = input
+            .get(tokens[i - 1].span.end..tokens[i].span.start)
+            .unwrap_or_default();
+
+        if is_break(&tokens[i - 1], gap) {
+            units.push(start..i);
+            start = i;
+        }
+    }
+    units.push(start..tokens.len());
+
+    units
+}
@@ -0,0 +1,227 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+const TRACKER_HOSTS: &[&str] = &["atlassian.net", "bugs.chromium.org", "youtrack.cloud"];
+const CI_HOSTS: &[&str] = &["travis-ci.org", "travis-ci.com", "circleci.com", "appveyor.com", "buildkite.com"];
+const DOCS_HOSTS: &[&str] = &["readthedocs.io", "github.io", "docs.rs", "pkg.go.dev", "godoc.org", "wikipedia.org"];
+const CODE_HOSTS: &[&str] = &[
+    "github.com",
+    "githubusercontent.com",
+    "gitlab.com",
+    "bitbucket.org",
+    "codeberg.org",
+    "sourceforge.net",
+    "googlesource.com",
+    "sr.ht",
+];
+
+// extensions of files that are named like hosts, as in `main.rs/` or `app.js/`
+const FILE_EXTENSIONS: &[&str] = &[
+    "c", "cc", "cpp", "cs", "css", "go", "h", "html", "java", "js", "json", "kt", "lock", "log", "md", "php", "py",
+    "rb", "rs", "sh", "toml", "ts", "txt", "xml", "yaml", "yml",
+];
+
+/// Whether `url`, with no scheme or `www.`, starts with a host: one of the
+/// known sites, or any `host.tld` that a path follows.
+fn bare_host(url: &str) -> bool {
+    let (host, path) = url.split_once('/').unwrap_or((url, ""));
+    let host = host.to_lowercase();
+    let Some((name, tld)) = host.rsplit_once('.') else {
+        return false;
+    };
+    let known = [TRACKER_HOSTS, CI_HOSTS, DOCS_HOSTS, CODE_HOSTS]
+        .iter()
+        .any(|hosts| hosts.iter().any(|h| host == *h || host.ends_with(&format!(".{h}"))));
+    let named = !name.is_empty() && tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic());
+
+    named && (known || (!path.is_empty() && !FILE_EXTENSIONS.contains(&tld)))
+}
+
+/// What kind of site a URL points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkCategory {
+    CodeHost,
+    Ci,
+    Docs,
+    IssueTracker,
+    Other,
+}
+
+impl LinkCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CodeHost => "code-host",
+            Self::Ci => "ci",
+            Self::Docs => "docs",
+            Self::IssueTracker => "issue-tracker",
+            Self::Other => "other",
+        }
+    }
+
+    /// Issue and pull request pages count as the issue tracker even on a code
+    /// host, and CI runs or wiki pages likewise win over the host.
+    fn of(domain: &str, path: &str) -> Self {
+        let path = path.to_lowercase();
+        let on = |hosts: &[&str]| hosts.iter().any(|h| domain == *h || domain.ends_with(&format!(".{h}")));
+        let prefixed = |prefixes: &[&str]| prefixes.iter().any(|p| domain.starts_with(p));
+        let in_path = |parts: &[&str]| parts.iter().any(|p| path.contains(p));
+
+        if in_path(&["/issues", "/pull/", "/pulls", "/merge_requests", "/show_bug.cgi"])
+            || prefixed(&["jira.", "bugs.", "bugzilla.", "issues."])
+            || on(TRACKER_HOSTS)
+        {
+            Self::IssueTracker
+        } else if in_path(&["/actions/runs", "/-/jobs/", "/-/pipelines", "/builds/"])
+            || prefixed(&["ci.", "jenkins.", "build.", "builds.", "prow.", "testgrid."])
+            || on(CI_HOSTS)
+        {
+            Self::Ci
+        } else if path.starts_with("/docs") || path.starts_with("/doc/") || path.contains("/wiki")
+            || prefixed(&["docs.", "doc.", "wiki.", "developer.", "learn."])
+            || on(DOCS_HOSTS)
+        {
+            Self::Docs
+        } else if prefixed(&["git."]) || on(CODE_HOSTS) {
+            Self::CodeHost
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// A URL found in the input, kept under
+/// [`CleanerConfig::keep_links`](crate::CleanerConfig::keep_links).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub url: String,
+    /// Lowercased host, without `www.`, credentials or port. Empty for
+    /// relative and `mailto:` links.
+    pub domain: String,
+    pub category: LinkCategory,
+    /// Byte range of the input the URL was taken from.
+    pub span: Range<usize>,
+    /// Index of the link token in the cleaned tokens.
+    pub position: usize,
+}
+
+impl Link {
+    /// The domain and category of `url`, with no span or position yet.
+    pub(crate) fn parse(url: &str) -> Self {
+        // relative links and `mailto:` have no host, and no domain
+        let (rest, has_host) = match url.split_once("://") {
+            Some((_, rest)) => (rest, true),
+            None => match url.strip_prefix("//") {
+                Some(rest) => (rest, true),
+                None => (url, url.get(..4).is_some_and(|w| w.eq_ignore_ascii_case("www.")) || bare_host(url)),
+            },
+        };
+        let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        let (host, path) = rest.split_at(end);
+        let host = host.rsplit('@').next().unwrap_or(host);
+        let host = match host.rsplit_once(':') {
+            Some((h, port)) if port.chars().all(|c| c.is_ascii_digit()) => h,
+            _ => host,
+        };
+        let domain = if has_host { host.to_lowercase() } else { String::new() };
+        let domain = domain.strip_prefix("www.").unwrap_or(&domain).to_owned();
+
+        Self {
+            url: url.to_owned(),
+            category: LinkCategory::of(&domain, path),
+            domain,
+            span: 0..0,
+            position: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LinkFinder {
+    rgx: Regex,
+}
+
+impl LinkFinder {
+    pub(crate) fn new() -> Self {
+        Self {
+            rgx: Regex::new(concat!(
+                r#"(?i)(?:^|[^\w.@/+-])((?:[a-z][a-z0-9+.-]*://|www\.)[^\s<>"'`\\]+"#,
+                r#"|(?:[a-z0-9-]+\.)+[a-z]{2,}(?:/[^\s<>"'`\\]*)?)"#,
+            ))
+            .unwrap(),
+        }
+    }
+
+    /// URLs in `text`, with a scheme, starting with `www.` or with a bare
+    /// host, ordered by position and without trailing punctuation.
+    pub(crate) fn find(&self, text: &str) -> Vec<Range<usize>> {
+        self.rgx
+            .captures_iter(text)
+            .filter_map(|c| c.get(1))
+            .map(|m| m.start()..m.start() + trim_end(m.as_str()).len())
+            .filter(|r| {
+                let url = &text[r.clone()];
+                let host = url.split_once("://").map_or(url, |(_, h)| h);
+                let schemed = url.contains("://") || url.get(..4).is_some_and(|w| w.eq_ignore_ascii_case("www."));
+                // a bare host is a whole word, not the start of `a.b_c`
+                let whole = !text[r.end..].starts_with(|c: char| c.is_alphanumeric() || c == '_');
+                host.contains(|c: char| c.is_alphanumeric())
+                    && url.len() > "www.".len()
+                    && (schemed || (whole && bare_host(url)))
+            })
+            .collect()
+    }
+}
+
+/// `url` without the sentence punctuation and unbalanced closing brackets
+/// that follow it in prose, like the `).` of `(see https://x.org/a_(b)).`
+fn trim_end(mut url: &str) -> &str {
+    loop {
+        let Some(c) = url.chars().last() else {
+            return url;
+        };
+        let unbalanced = |open: char| url.matches(open).count() < url.matches(c).count();
+        let trim = match c {
+            '.' | ',' | ';' | ':' | '!' | '?' | '*' | '_' | '~' => true,
+            ')' => unbalanced('('),
+            ']' => unbalanced('['),
+            '}' => unbalanced('{'),
+            _ => false,
+        };
+        if !trim {
+            return url;
+        }
+        url = &url[..url.len() - c.len_utf8()];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_links() {
+        let finder = LinkFinder::new();
+        let text = "see https://en.wikipedia.org/wiki/Rust_(language)), www.example.com. \
+                    and (http://user@GitHub.com:8080/a/b?x=1#l2) or std::io";
+        let found = finder.find(text).into_iter().map(|r| &text[r]).collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                "https://en.wikipedia.org/wiki/Rust_(language)",
+                "www.example.com",
+                "http://user@GitHub.com:8080/a/b?x=1#l2"
+            ]
+        );
+
+        let link = Link::parse(found[2]);
+        assert_eq!(link.domain, "github.com");
+        assert_eq!(link.category, LinkCategory::CodeHost);
+
+        let category = |url| Link::parse(url).category;
+        assert_eq!(category("https://gitlab.com/org/repo/-/issues/573"), LinkCategory::IssueTracker);
+        assert_eq!(category("https://prow.k8s.io/view/gcs/logs/1351218547929387008"), LinkCategory::Ci);
+        assert_eq!(category("https://docs.rs/regex"), LinkCategory::Docs);
+        assert_eq!(category("www.example.com"), LinkCategory::Other);
+    }
+}
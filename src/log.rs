@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::Entity;
+
+const MONTHS: &str = "jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec";
+
+/// Finds the volatile parts of log lines: timestamps, UUIDs, pointers,
+/// generated name suffixes and numbers.
+#[derive(Debug, Clone)]
+pub(crate) struct LogNormalizer {
+    line_rgx: Regex,
+    field_rgx: Regex,
+}
+
+impl LogNormalizer {
+    pub(crate) fn new() -> Self {
+        let time = r"\d{1,2}:\d{2}:\d{2}(?:[.,]\d+)?";
+        let line_rgx = Regex::new(&format!(
+            r"(?i)^\s*(?:\[?(?:trace|debug|info|warn|warning|error|fatal)\]?[\s:]|[iwef]\d{{4}}\s|\d{{4}}-\d{{2}}-\d{{2}}|(?:{MONTHS})[a-z]*\.?\s+\d{{1,2}}\s|{time}|time=|level=)"
+        ))
+        .unwrap();
+        let field_rgx = Regex::new(&format!(
+            r"(?i)(?P<ts>\b\d{{4}}-\d{{2}}-\d{{2}}[t ]{time}(?:z|[+-]\d{{2}}:?\d{{2}})?|\b(?:{MONTHS})[a-z]*\.?\s+\d{{1,2}},?\s+(?:\d{{4}}\s+)?{time}|\b[iwef]\d{{4}}\s+{time}|\b{time})|(?P<uuid>\b[0-9a-f]{{8}}-[0-9a-f]{{4}}-[0-9a-f]{{4}}-[0-9a-f]{{4}}-[0-9a-f]{{12}}\b)|(?P<hex>\b0x[0-9a-f]+\b)|(?P<id>-[a-z0-9]{{5}}\b)|(?P<num>\b\d+(?:\.\d+)?\b)"
+        ))
+        .unwrap();
+
+        Self { line_rgx, field_rgx }
+    }
+
+    /// The volatile fields within `range` of `text`. Lines sharing a block
+    /// with a log line count as log output too, like the indented dump or the
+    /// `step:` lines around a timestamped failure.
+    pub(crate) fn fields(&self, text: &str, range: Range<usize>) -> Vec<(Range<usize>, Entity)> {
+        // widen the range to the blank-line separated blocks around it
+        let mut start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        for line in text[..start].split_inclusive('\n').rev() {
+            if line.trim().is_empty() {
+                break;
+            }
+            start -= line.len();
+        }
+        let mut end = text[range.end..].find('\n').map_or(text.len(), |i| range.end + i + 1);
+        for line in text[end..].split_inclusive('\n') {
+            if line.trim().is_empty() {
+                break;
+            }
+            end += line.len();
+        }
+
+        let mut fields = vec![];
+        let mut block: Vec<(usize, &str)> = vec![];
+        let mut at = start;
+        for line in text[start..end].split_inclusive('\n').chain([""]) {
+            if !line.trim().is_empty() {
+                block.push((at, line));
+                at += line.len();
+                continue;
+            }
+            if block.iter().any(|(_, l)| self.line_rgx.is_match(l)) {
+                for (line_start, l) in &block {
+                    self.line_fields(l, *line_start, &mut fields);
+                }
+            }
+            block.clear();
+            at += line.len();
+        }
+
+        fields
+            .into_iter()
+            .filter(|(r, _)| range.start <= r.start && r.end <= range.end)
+            .map(|(r, e)| (r.start - range.start..r.end - range.start, e))
+            .collect()
+    }
+
+    /// `text` with its volatile fields replaced by their placeholders.
+    pub(crate) fn normalize(&self, text: &str) -> String {
+        let mut normalized = text.to_owned();
+        for (r, entity) in self.fields(text, 0..text.len()).into_iter().rev() {
+            // the dash of a suffix stays in the text
+            let start = if text[r.clone()].starts_with('-') { r.start + 1 } else { r.start };
+            normalized.replace_range(start..r.end, entity.placeholder());
+        }
+
+        normalized
+    }
+
+    /// Pushes the fields of `line`, found at `start`.
+    fn line_fields(&self, line: &str, start: usize, fields: &mut Vec<(Range<usize>, Entity)>) {
+        for c in self.field_rgx.captures_iter(line) {
+            let m = c.get(0).unwrap();
+            let r = start + m.start()..start + m.end();
+            let entity = if c.name("ts").is_some() {
+                Entity::Timestamp
+            } else if c.name("uuid").is_some() {
+                Entity::Uuid
+            } else if c.name("hex").is_some() {
+                Entity::Hex
+            } else if c.name("num").is_some() {
+                Entity::Number
+            } else {
+                // a suffix of some name, mixing letters and digits
+                let suffix = &m.as_str()[1..];
+                let named = line[..m.start()].ends_with(|c: char| c.is_alphanumeric());
+                if suffix.chars().all(|c| c.is_ascii_digit()) {
+                    fields.push((r.start + 1..r.end, Entity::Number));
+                    continue;
+                }
+                if !named || !suffix.chars().any(|c| c.is_ascii_digit()) {
+                    continue;
+                }
+                Entity::Id
+            };
+            // like an ID, a UUID suffix takes the dash that ties it to the name
+            let suffix = line[..m.start()].strip_suffix('-');
+            if entity == Entity::Uuid && suffix.is_some_and(|s| s.ends_with(|c: char| c.is_alphanumeric())) {
+                fields.push((r.start - 1..r.end, entity));
+                continue;
+            }
+            fields.push((r, entity));
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use pulldown_cmark::Tag;
+
+/// Markdown elements that can be given a [`TagPolicy`](crate::TagPolicy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MarkdownElement {
+    Heading,
+    BlockQuote,
+    List,
+    Table,
+    /// Emphasis, strong emphasis and strikethrough.
+    Emphasis,
+    Link,
+    Image,
+}
+
+impl MarkdownElement {
+    pub(crate) fn of(tag: &Tag) -> Option<Self> {
+        Some(match tag {
+            Tag::Heading(..) => MarkdownElement::Heading,
+            Tag::BlockQuote => MarkdownElement::BlockQuote,
+            Tag::List(_) => MarkdownElement::List,
+            Tag::Table(_) => MarkdownElement::Table,
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough => MarkdownElement::Emphasis,
+            Tag::Link(..) => MarkdownElement::Link,
+            Tag::Image(..) => MarkdownElement::Image,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MarkdownElement::Heading => "heading",
+            MarkdownElement::BlockQuote => "quote",
+            MarkdownElement::List => "list",
+            MarkdownElement::Table => "table",
+            MarkdownElement::Emphasis => "emphasis",
+            MarkdownElement::Link => "link",
+            MarkdownElement::Image => "image",
+        }
+    }
+}
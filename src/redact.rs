@@ -0,0 +1,195 @@
+use std::{collections::HashSet, ops::Range};
+
+use hmac_sha256::HMAC;
+use regex::Regex;
+
+use crate::RedactionMode;
+
+/// A kind of personal data removed by redaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PiiKind {
+    Email,
+    /// An `@user` mention.
+    Handle,
+    Phone,
+    /// An IPv4 or IPv6 address.
+    Ip,
+    /// The user name in a home directory path like `/users/<name>/`.
+    UserPath,
+}
+
+impl PiiKind {
+    /// Placeholder name; handles and home directories share one, so the same
+    /// user gets the same pseudonym in both.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Email => "EMAIL",
+            Self::Handle | Self::UserPath => "USER",
+            Self::Phone => "PHONE",
+            Self::Ip => "IP",
+        }
+    }
+}
+
+/// One redacted value, for auditing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    pub kind: PiiKind,
+    pub text: String,
+    pub replacement: String,
+    /// Byte range of the input the value was taken from.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Redactor {
+    // the pattern for each kind, and the group holding the value
+    detectors: Vec<(PiiKind, Regex, usize)>,
+    mode: RedactionMode,
+}
+
+impl Redactor {
+    /// Returns `None` when no kind is enabled.
+    pub(crate) fn new(enabled: &HashSet<PiiKind>, mode: RedactionMode) -> Option<Self> {
+        let patterns = [
+            (PiiKind::Email, r"\b[\w.+-]+@[\w-]+(?:\.[\w-]+)+\b", 0),
+            (PiiKind::Handle, r"(?:^|[^\w.@/])(@[a-z0-9][a-z0-9-]{0,38})\b", 1),
+            (
+                PiiKind::Phone,
+                r"\+\d{1,3}[ .-]?\(?\d{1,4}\)?(?:[ .-]?\d{2,4}){2,4}\b|\(\d{3}\)[ .-]?\d{3}[ .-]\d{4}\b|\b\d{3}[.-]\d{3}[.-]\d{4}\b",
+                0,
+            ),
+            (
+                PiiKind::Ip,
+                r"\b(?:\d{1,3}\.){3}\d{1,3}\b|\b(?:[0-9a-f]{1,4}:){7}[0-9a-f]{1,4}\b|\b(?:[0-9a-f]{1,4}:){2,}(?::[0-9a-f]{1,4})+\b|\b(?:[0-9a-f]{1,4}:)+(?::[0-9a-f]{1,4}){2,}\b",
+                0,
+            ),
+            (PiiKind::UserPath, r"[/\\](?:users|home)[/\\]([\w.-]+)", 1),
+        ];
+
+        let detectors = patterns
+            .into_iter()
+            .filter(|(kind, _, _)| enabled.contains(kind))
+            .map(|(kind, p, group)| (kind, Regex::new(&format!("(?i){p}")).unwrap(), group))
+            .collect::<Vec<_>>();
+        if detectors.is_empty() {
+            return None;
+        }
+
+        Some(Self { detectors, mode })
+    }
+
+    /// Personal data in `text`, ordered by position, without overlaps.
+    pub(crate) fn find(&self, text: &str) -> Vec<(Range<usize>, PiiKind)> {
+        let mut found = vec![];
+        for (kind, rgx, group) in &self.detectors {
+            for c in rgx.captures_iter(text) {
+                let Some(m) = c.get(*group) else {
+                    continue;
+                };
+                let real = match kind {
+                    PiiKind::Handle => is_mention(text, m.range()),
+                    PiiKind::Ip => !m.as_str().contains('.') || is_ipv4(text, m.range()),
+                    _ => true,
+                };
+                if real {
+                    found.push((m.range(), *kind));
+                }
+            }
+        }
+        found.sort_by_key(|(r, _)| (r.start, usize::MAX - r.end));
+
+        let mut end = 0;
+        found.retain(|(r, _)| {
+            let keep = r.start >= end;
+            end = end.max(r.end);
+            keep
+        });
+
+        found
+    }
+
+    /// The replacement for `text`, given what the document redacted so far.
+    pub(crate) fn replacement(&self, kind: PiiKind, text: &str, done: &[Redaction]) -> String {
+        let key = key(kind, text);
+        match &self.mode {
+            RedactionMode::Placeholder => format!("<{}>", kind.name()),
+            RedactionMode::DocumentPseudonym => {
+                let same_name = done.iter().filter(|r| r.kind.name() == kind.name());
+                if let Some(r) = same_name.clone().find(|r| key_of(r) == key) {
+                    return r.replacement.clone();
+                }
+
+                let mut seen = same_name.map(key_of).collect::<Vec<_>>();
+                seen.sort();
+                seen.dedup();
+                format!("<{}_{}>", kind.name(), seen.len() + 1)
+            }
+            RedactionMode::CorpusPseudonym { salt } => {
+                // keyed, so pseudonyms can't be reversed from a user name list
+                // without the salt, and 64 bits wide to keep users apart
+                let mac = HMAC::mac(key.as_bytes(), salt.as_bytes());
+                format!("<{}_{:016x}>", kind.name(), u64::from_be_bytes(mac[..8].try_into().unwrap()))
+            }
+        }
+    }
+}
+
+// Java and TypeScript annotations, written like mentions
+const ANNOTATIONS: &[&str] = &[
+    "Override", "Deprecated", "SuppressWarnings", "FunctionalInterface", "SafeVarargs", "Test", "Before", "After",
+    "BeforeEach", "AfterEach", "Autowired", "Bean", "Component", "Service", "Inject", "Injectable", "Nullable",
+    "NonNull", "Transactional", "Configuration", "Input", "Output", "NgModule",
+];
+
+/// Whether the `@name` at `at` in `text` mentions a user, rather than being a
+/// scoped npm package like `@types/react` or an annotation like `@Override`.
+pub(crate) fn is_mention(text: &str, at: Range<usize>) -> bool {
+    let name = text[at.clone()].trim_start_matches('@');
+    !text[at.end..].starts_with(['/', '(']) && !ANNOTATIONS.contains(&name)
+}
+
+/// Whether the dotted quad at `at` in `text`, with an optional port, is an
+/// address rather than a version like `.NET 4.7.2.0`: octets up to 255, a
+/// host part other than 0, and no `version` in front.
+pub(crate) fn is_ipv4(text: &str, at: Range<usize>) -> bool {
+    let quad = text[at.clone()].split(':').next().unwrap_or_default();
+    let octets = quad.split('.').map(|o| o.parse::<u8>().ok()).collect::<Vec<_>>();
+    let versioned = text[..at.start].trim_end().to_lowercase().ends_with("version");
+    octets.iter().all(Option::is_some) && octets.last() != Some(&Some(0)) && !versioned
+}
+
+fn key_of(r: &Redaction) -> String {
+    key(r.kind, &r.text)
+}
+
+/// What makes two values the same person: case never matters, and `@name`
+/// is the user of `/users/name/`.
+fn key(kind: PiiKind, text: &str) -> String {
+    let text = text.to_lowercase();
+    match kind {
+        PiiKind::Handle => text.trim_start_matches('@').to_owned(),
+        PiiKind::Phone => text.chars().filter(|c| c.is_ascii_digit()).collect(),
+        _ => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corpus_pseudonyms() {
+        let mode = RedactionMode::CorpusPseudonym { salt: "s".to_owned() };
+        let redactor = Redactor::new(&[PiiKind::Handle].into(), mode).unwrap();
+        let pseudonym = |name: String| redactor.replacement(PiiKind::Handle, &name, &[]);
+        assert_eq!(pseudonym("@Jo".to_owned()), pseudonym("@jo".to_owned()));
+        assert_eq!(pseudonym("@jo".to_owned()).len(), "<USER_>".len() + 16);
+
+        // distinct users keep distinct pseudonyms well past 2^16 of them
+        let mut seen = HashSet::new();
+        for i in 0..100_000 {
+            assert!(seen.insert(pseudonym(format!("@user{i}"))));
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::{redact::is_mention, Link, LinkCategory};
+
+/// What a cross-reference points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceKind {
+    Issue,
+    /// A pull or merge request. Only links tell these apart from issues, a
+    /// bare `#123` is always an issue.
+    PullRequest,
+    /// An `@user` mention.
+    Mention,
+    Commit,
+    /// Any other URL.
+    Url(LinkCategory),
+}
+
+impl ReferenceKind {
+    pub fn placeholder(&self) -> String {
+        match self {
+            Self::Issue => "<ISSUE>".to_owned(),
+            Self::PullRequest => "<PR>".to_owned(),
+            Self::Mention => "<MENTION>".to_owned(),
+            Self::Commit => "<COMMIT>".to_owned(),
+            Self::Url(category) => format!("<URL:{}>", category.name()),
+        }
+    }
+}
+
+/// A cross-reference found in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub kind: ReferenceKind,
+    /// What is referenced, lowercased so duplicates compare equal: `#123` or
+    /// `org/repo#123` for issues and pull requests, the user name for
+    /// mentions, the SHA or `org/repo@sha` for commits, and the URL otherwise.
+    pub target: String,
+    /// Byte range of the input the reference was taken from.
+    pub span: Range<usize>,
+    /// Index of the reference's token in the cleaned tokens.
+    pub position: usize,
+}
+
+/// Cross-references collected under
+/// [`CleanerConfig::references`](crate::CleanerConfig::references), each list
+/// in input order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct References {
+    /// Issues and pull requests, by number or link.
+    pub issues: Vec<Reference>,
+    pub mentions: Vec<Reference>,
+    /// Commits, by SHA or link.
+    pub commits: Vec<Reference>,
+    /// Links to anything else.
+    pub urls: Vec<Reference>,
+}
+
+impl References {
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty() && self.mentions.is_empty() && self.commits.is_empty() && self.urls.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Reference> {
+        self.issues
+            .iter()
+            .chain(&self.mentions)
+            .chain(&self.commits)
+            .chain(&self.urls)
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut Reference> {
+        self.issues
+            .iter_mut()
+            .chain(&mut self.mentions)
+            .chain(&mut self.commits)
+            .chain(&mut self.urls)
+    }
+
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(&Reference) -> bool) {
+        for list in [&mut self.issues, &mut self.mentions, &mut self.commits, &mut self.urls] {
+            list.retain(&mut f);
+        }
+    }
+
+    pub(crate) fn push(&mut self, reference: Reference) {
+        let list = match reference.kind {
+            ReferenceKind::Issue | ReferenceKind::PullRequest => &mut self.issues,
+            ReferenceKind::Mention => &mut self.mentions,
+            ReferenceKind::Commit => &mut self.commits,
+            ReferenceKind::Url(_) => &mut self.urls,
+        };
+        list.push(reference);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReferenceFinder {
+    rgx: Regex,
+}
+
+impl ReferenceFinder {
+    pub(crate) fn new() -> Self {
+        Self {
+            rgx: Regex::new(concat!(
+                r"(?i)(?:^|[^\w/&#@.-])",
+                r"(?:(?P<issue>(?:[\w.-]+/[\w.-]+)?#\d+)|@(?P<user>[a-z0-9][a-z0-9-]{0,38})|(?P<sha>[0-9a-f]{7,40}))\b",
+            ))
+            .unwrap(),
+        }
+    }
+
+    /// Issue references, mentions and commit SHAs in `text`, ordered by
+    /// position, with their targets.
+    pub(crate) fn find(&self, text: &str) -> Vec<(Range<usize>, ReferenceKind, String)> {
+        let mut found = vec![];
+        for c in self.rgx.captures_iter(text) {
+            if let Some(m) = c.name("issue") {
+                found.push((m.range(), ReferenceKind::Issue, m.as_str().to_lowercase()));
+            } else if let Some(m) = c.name("user") {
+                let at = m.start() - 1..m.end();
+                if is_mention(text, at.clone()) {
+                    found.push((at, ReferenceKind::Mention, m.as_str().to_lowercase()));
+                }
+            } else if let Some(m) = c.name("sha") {
+                // a SHA mixes digits and letters, and is not a piece of a UUID
+                let sha = m.as_str();
+                let mixed = sha.contains(|c: char| c.is_ascii_digit()) && sha.contains(|c: char| c.is_alphabetic());
+                if mixed && !text[m.end()..].starts_with('-') {
+                    found.push((m.range(), ReferenceKind::Commit, sha.to_lowercase()));
+                }
+            }
+        }
+
+        found
+    }
+}
+
+/// What a link points to: an issue, pull request or commit on a code host
+/// such as `github.com/org/repo/pull/12` or `gitlab.com/group/repo/-/issues/573`,
+/// or else just the URL.
+pub(crate) fn link_reference(link: &Link) -> (ReferenceKind, String) {
+    let path = link.url.split_once("://").map_or(link.url.as_str(), |(_, rest)| rest);
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let segments = path
+        .split('/')
+        .skip(1)
+        .filter(|s| !s.is_empty() && *s != "-")
+        .collect::<Vec<_>>();
+
+    let found = segments.iter().enumerate().skip(2).find_map(|(i, s)| {
+        let id = segments.get(i + 1)?;
+        let repo = segments[..i].join("/").to_lowercase();
+        let number = id.chars().all(|c| c.is_ascii_digit());
+        let sha = (7..=40).contains(&id.len()) && id.chars().all(|c| c.is_ascii_hexdigit());
+        let (kind, sep) = match *s {
+            "issues" if number => (ReferenceKind::Issue, '#'),
+            "pull" | "pulls" | "merge_requests" if number => (ReferenceKind::PullRequest, '#'),
+            "commit" | "commits" if sha => (ReferenceKind::Commit, '@'),
+            _ => return None,
+        };
+        Some((kind, format!("{repo}{sep}{}", id.to_lowercase())))
+    });
+
+    match found {
+        Some(found) if !link.domain.is_empty() => found,
+        _ => (ReferenceKind::Url(link.category), link.url.clone()),
+    }
+}
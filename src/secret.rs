@@ -0,0 +1,201 @@
+use std::ops::Range;
+
+use regex::Regex;
+
+/// A kind of credential removed by secret scrubbing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecretKind {
+    AwsKey,
+    GithubToken,
+    SlackToken,
+    Jwt,
+    PrivateKey,
+    /// The value of a `password=`, `token:` or similar assignment.
+    Assignment,
+    /// A long random-looking token matching no known format.
+    HighEntropy,
+}
+
+impl SecretKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AwsKey => "aws-key",
+            Self::GithubToken => "github-token",
+            Self::SlackToken => "slack-token",
+            Self::Jwt => "jwt",
+            Self::PrivateKey => "private-key",
+            Self::Assignment => "password",
+            Self::HighEntropy => "high-entropy",
+        }
+    }
+
+    pub fn placeholder(&self) -> String {
+        format!("<SECRET:{}>", self.name())
+    }
+}
+
+/// Where a secret was found. The value itself is never kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Secret {
+    pub kind: SecretKind,
+    /// Byte range of the input the secret was taken from.
+    pub span: Range<usize>,
+}
+
+// tokens shorter than this are never flagged by entropy alone
+const MIN_ENTROPY_LEN: usize = 20;
+
+#[derive(Debug, Clone)]
+pub(crate) struct SecretScrubber {
+    // the pattern for each kind, and the group holding the secret
+    patterns: Vec<(SecretKind, Regex, usize)>,
+    min_entropy: Option<f64>,
+}
+
+impl SecretScrubber {
+    pub(crate) fn new(min_entropy: Option<f64>) -> Self {
+        let patterns = [
+            (
+                SecretKind::PrivateKey,
+                r"-----BEGIN [A-Z ]*PRIVATE KEY-----(?s:.*?)(?:-----END [A-Z ]*PRIVATE KEY-----|\z)",
+                0,
+            ),
+            (
+                SecretKind::AwsKey,
+                r"\b(?:AKIA|ASIA|AGPA|AIDA|AROA|ANPA|ANVA|AIPA)[0-9A-Z]{16}\b",
+                0,
+            ),
+            (
+                SecretKind::GithubToken,
+                r"\b(?:gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{22,})\b",
+                0,
+            ),
+            (SecretKind::SlackToken, r"\bxox[abposr]-[A-Za-z0-9-]{10,}", 0),
+            (
+                SecretKind::Jwt,
+                r"\beyJ[A-Za-z0-9_-]+\.eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+",
+                0,
+            ),
+            (
+                SecretKind::Assignment,
+                r#"(?i)\b(?:password|passwd|pwd|secret|token|api[_-]?key|access[_-]?key|secret[_-]?key|aws_secret_access_key)["']?\s*[=:]\s*(["']?)([^\s"',;=][^\s"',;]*)"#,
+                2,
+            ),
+        ];
+
+        Self {
+            patterns: patterns
+                .into_iter()
+                .map(|(kind, p, group)| (kind, Regex::new(p).unwrap(), group))
+                .collect(),
+            min_entropy,
+        }
+    }
+
+    /// Secrets in `text`, ordered by position, without overlaps.
+    pub(crate) fn find(&self, text: &str) -> Vec<(Range<usize>, SecretKind)> {
+        let mut found = vec![];
+        for (kind, rgx, group) in &self.patterns {
+            for c in rgx.captures_iter(text) {
+                // only a quoted or credential-like value, not the prose of
+                // `the token: it expired` or code like `token = getToken();`
+                if *kind == SecretKind::Assignment && c[1].is_empty() && !credential_like(&c[2]) {
+                    continue;
+                }
+                if let Some(m) = c.get(*group) {
+                    found.push((m.range(), *kind));
+                }
+            }
+        }
+
+        if let Some(min_entropy) = self.min_entropy {
+            let mut start = 0;
+            for word in text.split_inclusive(char::is_whitespace) {
+                let trimmed = word.trim_end();
+                let inner = trimmed.trim_matches(|c: char| !c.is_alphanumeric() && !"+/=_-".contains(c));
+                let at = start + trimmed.find(inner).unwrap_or(0);
+                if looks_random(inner, min_entropy) {
+                    found.push((at..at + inner.len(), SecretKind::HighEntropy));
+                }
+                start += word.len();
+            }
+        }
+
+        found.sort_by_key(|(r, _)| (r.start, usize::MAX - r.end));
+        let mut end = 0;
+        found.retain(|(r, _)| {
+            let keep = r.start >= end;
+            end = end.max(r.end);
+            keep
+        });
+
+        found
+    }
+}
+
+/// A value long enough and with some digit or symbol, as chosen passwords and
+/// generated tokens have, and no call or plain name like `user_pass`.
+fn credential_like(s: &str) -> bool {
+    s.len() >= 6
+        && !s.contains('(')
+        && s.chars().any(|c| c.is_ascii_digit() || !(c.is_alphanumeric() || "_.)".contains(c)))
+}
+
+/// Whether `s` reads as a camelCase or dotted name, like `OAuth2ServerConfig`
+/// or `com.amazonaws.services.s3.AmazonS3Client`: each hump a word or a short
+/// acronym, with digits only at its end.
+fn is_identifier(s: &str) -> bool {
+    s.split('.').all(|segment| {
+        let mut humps = vec![];
+        let mut start = 0;
+        let mut prev = ' ';
+        for (i, c) in segment.char_indices() {
+            if c.is_ascii_uppercase() && (prev.is_ascii_lowercase() || prev.is_ascii_digit()) {
+                humps.push(&segment[start..i]);
+                start = i;
+            }
+            prev = c;
+        }
+        humps.push(&segment[start..]);
+
+        humps.iter().enumerate().all(|(i, hump)| {
+            let letters = hump.trim_end_matches(|c: char| c.is_ascii_digit());
+            let upper = letters.chars().take_while(char::is_ascii_uppercase).count();
+            let lower = letters.chars().skip(upper).take_while(char::is_ascii_lowercase).count();
+            !letters.is_empty()
+                && upper + lower == letters.len()
+                && (lower >= 2 || (lower == 0 && upper <= 3) || (i == 0 && upper == 0))
+        })
+    })
+}
+
+/// Mixed-case alphanumerics with high Shannon entropy. URLs, paths and hex
+/// strings such as commit hashes are left alone.
+fn looks_random(s: &str, min_entropy: f64) -> bool {
+    if s.len() < MIN_ENTROPY_LEN || s.contains("://") || s.starts_with('/') || s.contains("..") {
+        return false;
+    }
+    let has = |f: fn(&char) -> bool| s.chars().any(|c| f(&c));
+    if !(has(char::is_ascii_lowercase) && has(char::is_ascii_uppercase) && has(char::is_ascii_digit)) {
+        return false;
+    }
+    if is_identifier(s) {
+        return false;
+    }
+
+    let mut counts = [0usize; 256];
+    for b in s.bytes() {
+        counts[b as usize] += 1;
+    }
+    let len = s.len() as f64;
+    let entropy = counts
+        .iter()
+        .filter(|n| **n > 0)
+        .map(|n| {
+            let p = *n as f64 / len;
+            -p * p.log2()
+        })
+        .sum::<f64>();
+
+    entropy >= min_entropy
+}
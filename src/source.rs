@@ -0,0 +1,107 @@
+use std::ops::Range;
+
+/// Text handed to the tokenizer together with a map back to byte offsets in the
+/// original input.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MappedText {
+    pub(crate) text: String,
+    segs: Vec<Seg>,
+    /// Treat every whitespace char as a plain space, as HTML rendering does.
+    pub(crate) collapse_ws: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Seg {
+    at: usize,
+    src: Range<usize>,
+    // byte `at + n` of `text` is byte `src.start + n` of the input
+    exact: bool,
+}
+
+impl MappedText {
+    pub(crate) fn exact(text: &str, base: usize) -> Self {
+        let mut m = Self::default();
+        m.push_exact(text, base);
+        m
+    }
+
+    pub(crate) fn push_exact(&mut self, s: &str, src_start: usize) {
+        self.segs.push(Seg {
+            at: self.text.len(),
+            src: src_start..src_start + s.len(),
+            exact: true,
+        });
+        self.text.push_str(s);
+    }
+
+    /// Appends text that has no byte-for-byte counterpart in the input; every
+    /// position in it maps to `src`.
+    pub(crate) fn push_synthetic(&mut self, s: &str, src: Range<usize>) {
+        self.segs.push(Seg {
+            at: self.text.len(),
+            src,
+            exact: false,
+        });
+        self.text.push_str(s);
+    }
+
+    /// Shifts every source offset by `base`.
+    pub(crate) fn offset(&mut self, base: usize) {
+        for seg in &mut self.segs {
+            seg.src = seg.src.start + base..seg.src.end + base;
+        }
+    }
+
+    /// Maps everything to `src`, for text whose origin is only known as a whole.
+    pub(crate) fn collapse(&mut self, src: Range<usize>) {
+        for seg in &mut self.segs {
+            seg.src = src.clone();
+            seg.exact = false;
+        }
+    }
+
+    /// Maps a byte range of `text` to a byte range of the input.
+    pub(crate) fn span(&self, r: Range<usize>) -> Range<usize> {
+        let Some(first) = self.seg_at(r.start) else {
+            return 0..0;
+        };
+        let start = if first.exact {
+            first.src.start + (r.start - first.at)
+        } else {
+            first.src.start
+        };
+
+        let last = self.seg_at(r.end.saturating_sub(1).max(r.start)).unwrap_or(first);
+        let end = if last.exact {
+            last.src.start + (r.end - last.at)
+        } else {
+            last.src.end
+        };
+
+        start..end.max(start)
+    }
+
+    fn seg_at(&self, pos: usize) -> Option<&Seg> {
+        let i = self.segs.partition_point(|s| s.at <= pos);
+        self.segs.get(i.checked_sub(1)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span() {
+        let mut m = MappedText::exact("hello", 10);
+        m.push_synthetic(" . ", 15..15);
+        m.push_synthetic("a < b", 20..30);
+        m.push_exact("world", 40);
+
+        assert_eq!(m.span(1..4), 11..14);
+        assert_eq!(m.span(6..7), 15..15);
+        assert_eq!(m.span(9..10), 20..30);
+        assert_eq!(m.span(13..17), 40..44);
+        assert_eq!(m.span(0..18), 10..45);
+    }
+}
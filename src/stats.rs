@@ -0,0 +1,105 @@
+use crate::{SpannedToken, Token};
+
+/// Why a document shrank while cleaning.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanStats {
+    pub dropped: DropStats,
+    pub emitted: TokenStats,
+}
+
+/// Input pieces that were removed or replaced, by reason.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DropStats {
+    /// Words in a script outside the configured allowlist.
+    pub disallowed_script: usize,
+    /// Words confidently detected as a language outside the allowlist.
+    pub disallowed_lang: usize,
+    /// Words repeating the one right before them.
+    pub duplicate: usize,
+    /// Words replaced with the long-text placeholder.
+    pub over_length: usize,
+    /// Inline code, code blocks, code elements and detected code sections.
+    pub code: usize,
+    /// Words inside ignored headings.
+    pub heading: usize,
+    /// Words inside other ignored or replaced markdown elements, and in dropped
+    /// HTML comments.
+    pub markup: usize,
+    /// Words of issue-template boilerplate.
+    pub template: usize,
+    /// URLs replaced with a link token.
+    pub url: usize,
+    /// Stack traces replaced with a summary.
+    pub trace: usize,
+}
+
+/// Emitted tokens, by kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenStats {
+    pub word: usize,
+    /// Words of a single char, also counted in `word`.
+    pub short_word: usize,
+    pub punct: usize,
+    pub separator: usize,
+    pub link: usize,
+    pub image: usize,
+    pub code: usize,
+    pub code_block: usize,
+    pub code_section: usize,
+    pub element: usize,
+    pub section: usize,
+    pub label: usize,
+    pub trace: usize,
+    pub frame: usize,
+    pub entity: usize,
+    pub reference: usize,
+    pub long_text: usize,
+    pub redacted: usize,
+}
+
+impl TokenStats {
+    pub(crate) fn count(tokens: &[SpannedToken]) -> Self {
+        let mut stats = Self::default();
+        for t in tokens {
+            let n = match t.token {
+                Token::Word(_) => &mut stats.word,
+                Token::Punct(_) => &mut stats.punct,
+                Token::Separator => &mut stats.separator,
+                Token::Link | Token::Url(_) => &mut stats.link,
+                Token::Image => &mut stats.image,
+                Token::Code => &mut stats.code,
+                Token::CodeBlock(_) => &mut stats.code_block,
+                Token::CodeSection => &mut stats.code_section,
+                Token::Element(_) => &mut stats.element,
+                Token::Section(_) => &mut stats.section,
+                Token::Label(..) => &mut stats.label,
+                Token::Trace(_) => &mut stats.trace,
+                Token::Frame(_) => &mut stats.frame,
+                Token::Entity(_) => &mut stats.entity,
+                Token::Reference(_) => &mut stats.reference,
+                Token::LongText => &mut stats.long_text,
+                Token::Redacted(_) => &mut stats.redacted,
+            };
+            *n += 1;
+            if matches!(&t.token, Token::Word(w) if w.chars().count() == 1) {
+                stats.short_word += 1;
+            }
+        }
+
+        stats
+    }
+}
+
+impl CleanStats {
+    /// The `(added, ignored)` pair reported by the free `clean_text*` functions.
+    /// As before tokens existed, `added` counts links and words longer than
+    /// one char, repeats included, and `ignored` the words dropped for their
+    /// language or script.
+    pub fn added_ignored(&self) -> (usize, usize) {
+        let words = self.emitted.word - self.emitted.short_word + self.dropped.duplicate;
+        let added = words + self.emitted.link + self.emitted.long_text;
+        let ignored = self.dropped.disallowed_script + self.dropped.disallowed_lang;
+
+        (added, ignored)
+    }
+}
@@ -0,0 +1,203 @@
+use std::{borrow::Cow, collections::HashSet, ops::Range};
+
+use regex::Regex;
+
+/// Issue-template boilerplate removed from markdown before it is parsed.
+/// Nothing is removed by default; see [`TemplateConfig::issue_templates`].
+#[derive(Debug, Clone, Default)]
+pub struct TemplateConfig {
+    /// Remove `<!-- ... -->` comments.
+    pub comments: bool,
+    /// Remove headings and bold labels found in common issue templates.
+    pub known_headings: bool,
+    /// Remove unchecked checklist items such as `- [ ] tested on main`.
+    pub unchecked_items: bool,
+    /// Lines of these documents are removed wherever they appear in the input.
+    pub templates: Vec<String>,
+}
+
+impl TemplateConfig {
+    /// Everything the built-in patterns recognize.
+    pub fn issue_templates() -> Self {
+        Self {
+            comments: true,
+            known_headings: true,
+            unchecked_items: true,
+            templates: vec![],
+        }
+    }
+}
+
+// headings of the GitHub and GitLab issue templates seen most often, normalized
+// by `normalize`
+const KNOWN_HEADINGS: &[&str] = &[
+    "what version of go are you using",
+    "what version of cue are you using",
+    "does this issue reproduce with the latest release",
+    "what operating system and processor architecture are you using",
+    "go env output",
+    "what did you do",
+    "what did you expect to see",
+    "what did you see instead",
+    "describe the bug",
+    "to reproduce",
+    "steps to reproduce",
+    "expected behavior",
+    "expected behaviour",
+    "actual behavior",
+    "actual behaviour",
+    "screenshots",
+    "additional context",
+    "environment",
+    "description",
+    "which jobs are flaking",
+    "which tests are flaking",
+    "testgrid link",
+    "reason for failure",
+    "anything else we need to know",
+    "is your feature request related to a problem please describe",
+    "describe the solution youd like",
+    "describe alternatives youve considered",
+];
+
+#[derive(Debug, Clone)]
+pub(crate) struct TemplateStripper {
+    config: TemplateConfig,
+    comment_rgx: Regex,
+    heading_rgx: Regex,
+    label_rgx: Regex,
+    unchecked_rgx: Regex,
+    lines: HashSet<String>,
+}
+
+impl TemplateStripper {
+    pub(crate) fn new(config: TemplateConfig) -> Self {
+        let lines = config
+            .templates
+            .iter()
+            .flat_map(|t| t.lines())
+            .map(|l| l.trim().to_lowercase())
+            // lines like "```" or "---" would take document structure with them
+            .filter(|l| l.chars().any(char::is_alphanumeric))
+            .collect();
+
+        Self {
+            config,
+            comment_rgx: Regex::new(r"(?s)<!--.*?(-->|$)").unwrap(),
+            heading_rgx: Regex::new(r"(?m)^ {0,3}#{1,6}[ \t]+(.*?)[ \t#]*$").unwrap(),
+            label_rgx: Regex::new(r"(?m)^[ \t]*\*\*([^*\n]+)\*\*[ \t]*:?").unwrap(),
+            unchecked_rgx: Regex::new(r"(?m)^[ \t]*[-*+][ \t]+\[ \][^\n]*$").unwrap(),
+            lines,
+        }
+    }
+
+    /// Blanks out boilerplate, keeping every byte offset and line break of
+    /// `text` intact. Returns the stripped text and the number of words removed.
+    pub(crate) fn strip<'a>(&self, text: &'a str) -> (Cow<'a, str>, usize) {
+        let mut regions: Vec<Range<usize>> = vec![];
+        if self.config.comments {
+            regions.extend(self.comment_rgx.find_iter(text).map(|m| m.range()));
+        }
+
+        if self.config.known_headings {
+            for rgx in [&self.heading_rgx, &self.label_rgx] {
+                for c in rgx.captures_iter(text) {
+                    if KNOWN_HEADINGS.contains(&normalize(&c[1]).as_str()) {
+                        regions.push(c.get(0).unwrap().range());
+                    }
+                }
+            }
+        }
+
+        if self.config.unchecked_items {
+            regions.extend(self.unchecked_rgx.find_iter(text).map(|m| m.range()));
+        }
+
+        if !self.lines.is_empty() {
+            let mut start = 0;
+            for line in text.split_inclusive('\n') {
+                if self.lines.contains(&line.trim().to_lowercase()) {
+                    regions.push(start..start + line.trim_end().len());
+                }
+                start += line.len();
+            }
+        }
+
+        if regions.is_empty() {
+            return (Cow::Borrowed(text), 0);
+        }
+
+        let mut bytes = text.as_bytes().to_vec();
+        let mut blanked = vec![false; bytes.len()];
+        let mut words = 0;
+        for r in regions {
+            if blanked[r.clone()].iter().all(|b| *b) {
+                continue;
+            }
+            words += text[r.clone()].split_whitespace().count();
+            for i in r {
+                blanked[i] = true;
+                if !bytes[i].is_ascii_whitespace() {
+                    bytes[i] = b' ';
+                }
+            }
+        }
+
+        // every region starts and ends on a char boundary and only whole chars
+        // were overwritten with ASCII, so this is still valid UTF-8
+        let stripped = String::from_utf8(bytes).unwrap_or_else(|_| text.to_owned());
+        (Cow::Owned(stripped), words)
+    }
+}
+
+/// Lowercase words of `heading`, without a trailing aside such as the
+/// `` (`go version`) `` of "What version of Go are you using (`go version`)?".
+fn normalize(heading: &str) -> String {
+    let mut heading = heading;
+    loop {
+        heading = heading.trim_end_matches(|c: char| c.is_whitespace() || matches!(c, '?' | ':' | '.' | '!'));
+        let open = match heading.chars().last() {
+            Some(')') => '(',
+            Some('`') => '`',
+            _ => break,
+        };
+        match heading[..heading.len() - 1].rfind(open) {
+            Some(at) => heading = &heading[..at],
+            None => break,
+        }
+    }
+
+    heading
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_templates() {
+        let stripper = TemplateStripper::new(TemplateConfig {
+            templates: vec!["Please describe the problem.\n```\n".to_owned()],
+            ..TemplateConfig::issue_templates()
+        });
+
+        let text = "<!-- please answer these questions -->\n### Does this issue reproduce with the latest release?\n\nyep\n\n\
+                    ### What version of Go are you using (`go version`)?\n\n\
+                    **which jobs are flaking**: `ci-e2e`\n- [ ] tested on main\n- [x] searched issues\nplease describe the problem.\n";
+        let (stripped, words) = stripper.strip(text);
+
+        assert_eq!(stripped.len(), text.len());
+        assert_eq!(
+            stripped.split_whitespace().collect::<Vec<_>>(),
+            ["yep", "`ci-e2e`", "-", "[x]", "searched", "issues"]
+        );
+        assert_eq!(words, 39);
+    }
+}
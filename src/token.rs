@@ -0,0 +1,87 @@
+use std::{borrow::Cow, ops::Range};
+
+use whatlang::Lang;
+
+use crate::{CleanerConfig, Entity, MarkdownElement, ReferenceKind, TraceLang};
+
+/// A single unit of cleaned output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// Text taken from the input.
+    Word(String),
+    /// A punctuation character the input was split on.
+    Punct(char),
+    /// A line or block boundary.
+    Separator,
+    /// A URL, rendered as the link placeholder.
+    Link,
+    /// A URL reduced to its domain or category, rendered as `<URL:...>`.
+    Url(String),
+    /// A markdown image.
+    Image,
+    /// Inline code or a code element.
+    Code,
+    /// A markdown code block, with the language declared on its fence.
+    CodeBlock(Option<String>),
+    /// A region that the code detector replaced.
+    CodeSection,
+    /// A markdown element replaced as a whole.
+    Element(MarkdownElement),
+    /// Marks the start of a markdown element kept as a section.
+    Section(MarkdownElement),
+    /// The cleaned text of a markdown element, such as a heading.
+    Label(MarkdownElement, String),
+    /// Marks the start of a stack trace summary.
+    Trace(TraceLang),
+    /// A stack frame in a trace summary, as `function@file`.
+    Frame(String),
+    /// A volatile or technical value, rendered as its typed placeholder.
+    Entity(Entity),
+    /// An issue, pull request, mention or commit, rendered as its placeholder.
+    Reference(ReferenceKind),
+    /// A word longer than `max_token_chars`.
+    LongText,
+    /// Text removed by a redaction stage, holding its replacement.
+    Redacted(String),
+}
+
+impl Token {
+    /// Whether the token was produced by the cleaner rather than taken from the input.
+    pub fn is_synthetic(&self) -> bool {
+        !matches!(self, Token::Word(_) | Token::Punct(_))
+    }
+
+    /// Renders the token the way the string based API reports it.
+    pub fn render<'a>(&'a self, config: &'a CleanerConfig) -> Cow<'a, str> {
+        match self {
+            Token::Word(w) => Cow::Borrowed(w),
+            Token::Punct(c) => Cow::Owned(c.to_string()),
+            Token::Separator => Cow::Borrowed(&config.separator),
+            Token::Link => Cow::Borrowed(&config.link_placeholder),
+            Token::Url(u) => Cow::Owned(format!("<URL:{u}>")),
+            Token::Image => Cow::Borrowed(&config.image_placeholder),
+            Token::Code => Cow::Borrowed(&config.code_placeholder),
+            Token::CodeBlock(None) => Cow::Borrowed(&config.code_placeholder),
+            Token::CodeBlock(Some(lang)) => Cow::Owned(format!("{}:{lang}", config.code_placeholder)),
+            Token::CodeSection => Cow::Borrowed(&config.code_section_placeholder),
+            Token::Element(e) => Cow::Borrowed(e.name()),
+            Token::Section(e) => Cow::Owned(format!("[{}]", e.name().to_uppercase())),
+            Token::Label(e, label) => Cow::Owned(format!("[{}:{label}]", e.name().to_uppercase())),
+            Token::Trace(lang) => Cow::Owned(format!("trace:{}", lang.name())),
+            Token::Frame(f) => Cow::Borrowed(f),
+            Token::Entity(e) => Cow::Borrowed(e.placeholder()),
+            Token::Reference(r) => Cow::Owned(r.placeholder()),
+            Token::LongText => Cow::Borrowed(&config.long_text_placeholder),
+            Token::Redacted(r) => Cow::Borrowed(r),
+        }
+    }
+}
+
+/// A token and the byte range of the input it was produced from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Range<usize>,
+    /// Detected language of a word, when language detection ran on it.
+    pub lang: Option<Lang>,
+}
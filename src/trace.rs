@@ -0,0 +1,340 @@
+use std::{fmt, ops::Range};
+
+/// The runtime a stack trace came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraceLang {
+    Go,
+    Java,
+    Python,
+    JavaScript,
+    Rust,
+    DotNet,
+}
+
+impl TraceLang {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Go => "go",
+            Self::Java => "java",
+            Self::Python => "python",
+            Self::JavaScript => "javascript",
+            Self::Rust => "rust",
+            Self::DotNet => "dotnet",
+        }
+    }
+}
+
+/// One call in a stack trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    pub function: String,
+    /// File name without its directory.
+    pub file: Option<String>,
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}@{}", self.function, file),
+            None => f.write_str(&self.function),
+        }
+    }
+}
+
+/// A stack trace, innermost frame first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackTrace {
+    pub lang: TraceLang,
+    /// The panic or exception message, including the exception type.
+    pub message: String,
+    pub frames: Vec<StackFrame>,
+    /// Byte range of the text the trace covers.
+    pub span: Range<usize>,
+}
+
+type Line<'a> = (usize, &'a str);
+
+/// Finds Go, Java, Python, JavaScript, Rust and .NET stack traces in `text`.
+pub fn detect_traces(text: &str) -> Vec<StackTrace> {
+    let mut lines = vec![];
+    let mut pos = 0;
+    for line in text.split_inclusive('\n') {
+        lines.push((pos, line.trim_end()));
+        pos += line.len();
+    }
+
+    let mut traces = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let found = go_trace(&lines, i)
+            .or_else(|| python_trace(&lines, i))
+            .or_else(|| rust_trace(&lines, i))
+            .or_else(|| at_trace(&lines, i));
+        match found {
+            Some((trace, end)) => {
+                traces.push(trace);
+                i = end;
+            }
+            None => i += 1,
+        }
+    }
+
+    traces
+}
+
+/// The trace over `lines[start..end]`.
+fn trace(
+    lines: &[Line],
+    start: usize,
+    end: usize,
+    lang: TraceLang,
+    message: &str,
+    frames: Vec<StackFrame>,
+) -> (StackTrace, usize) {
+    let (last_at, last) = lines[end - 1];
+    let trace = StackTrace {
+        lang,
+        message: message.trim().to_owned(),
+        frames,
+        span: lines[start].0..last_at + last.len(),
+    };
+
+    (trace, end)
+}
+
+fn frame(function: &str, location: Option<&str>) -> StackFrame {
+    // `path/file.ext:12`, `path/file.ext:12:5` or `path/file.ext:line 12`
+    let (file, line) = match location {
+        Some(loc) => {
+            let mut parts = loc.rsplitn(3, ':').collect::<Vec<_>>();
+            parts.reverse();
+            let num = |s: &str| s.trim().trim_start_matches("line ").parse::<usize>().ok();
+            match parts[..] {
+                [path, l, c] if num(l).is_some() && num(c).is_some() => (Some(path), num(l)),
+                [.., path, l] if num(l).is_some() => (Some(path), num(l)),
+                _ => (Some(loc), None),
+            }
+        }
+        None => (None, None),
+    };
+    let file = file
+        .map(|f| f.rsplit(['/', '\\']).next().unwrap_or(f).to_owned())
+        .filter(|f| !f.is_empty());
+
+    StackFrame {
+        function: function.trim().to_owned(),
+        file,
+        line,
+    }
+}
+
+/// `panic: msg` or `fatal error: msg`, then `goroutine 1 [running]:` and pairs
+/// of `pkg.fn(args)` and `\t/path/file.go:12 +0x1d` lines.
+fn go_trace(lines: &[Line], i: usize) -> Option<(StackTrace, usize)> {
+    let first = lines[i].1.trim_start();
+    let message = first
+        .strip_prefix("panic: ")
+        .or_else(|| first.strip_prefix("fatal error: "))?;
+
+    let mut j = i + 1;
+    while j < lines.len() && j <= i + 3 && !lines[j].1.starts_with("goroutine ") {
+        j += 1;
+    }
+    if !lines.get(j)?.1.starts_with("goroutine ") {
+        return None;
+    }
+
+    let mut frames = vec![];
+    let mut first_goroutine = true;
+    j += 1;
+    while j < lines.len() {
+        let line = lines[j].1;
+        if line.trim().is_empty() && lines.get(j + 1).is_some_and(|l| l.1.starts_with("goroutine ")) {
+            first_goroutine = false;
+            j += 2;
+            continue;
+        }
+
+        let Some(loc) = lines
+            .get(j + 1)
+            .map(|l| l.1)
+            .filter(|l| l.starts_with([' ', '\t']) && l.contains(".go:"))
+        else {
+            break;
+        };
+        let function = line.trim().trim_start_matches("created by ");
+        let function = function.split(" in goroutine ").next().unwrap_or(function);
+        let function = match function.rfind('(') {
+            Some(p) if function.ends_with(')') => &function[..p],
+            _ => function,
+        };
+        let loc = loc.trim().split(" +0x").next().unwrap_or_default();
+        if first_goroutine && !function.starts_with("runtime.") && function != "panic" {
+            frames.push(frame(function, Some(loc)));
+        }
+        j += 2;
+    }
+
+    if lines.get(j).is_some_and(|l| l.1.starts_with("exit status ")) {
+        j += 1;
+    }
+
+    Some(trace(lines, i, j, TraceLang::Go, message, frames))
+}
+
+/// `Traceback (most recent call last):`, indented `File "x.py", line 3, in fn`
+/// frames and their source, then `ValueError: msg`.
+fn python_trace(lines: &[Line], i: usize) -> Option<(StackTrace, usize)> {
+    if lines[i].1.trim() != "Traceback (most recent call last):" {
+        return None;
+    }
+
+    let mut frames = vec![];
+    let mut j = i + 1;
+    while j < lines.len() && lines[j].1.starts_with([' ', '\t']) {
+        let line = lines[j].1.trim();
+        if let Some(rest) = line.strip_prefix("File \"") {
+            let (path, rest) = rest.split_once('"')?;
+            let line_no = rest.split("line ").nth(1).and_then(|l| l.split(',').next());
+            let function = rest.split(", in ").nth(1).unwrap_or("<module>");
+            let loc = line_no.map(|l| format!("{path}:{l}"));
+            frames.push(frame(function, Some(loc.as_deref().unwrap_or(path))));
+        }
+        j += 1;
+    }
+
+    let message = lines.get(j).map(|l| l.1).filter(|l| !l.trim().is_empty())?;
+    frames.reverse();
+
+    Some(trace(lines, i, j + 1, TraceLang::Python, message, frames))
+}
+
+/// `thread 'main' panicked at src/main.rs:2:5:` followed by the message, or
+/// `thread 'main' panicked at 'msg', src/main.rs:2:5`, then an optional
+/// `stack backtrace:` of `  0: fn` and `at ./src/file.rs:2:5` lines.
+fn rust_trace(lines: &[Line], i: usize) -> Option<(StackTrace, usize)> {
+    let first = lines[i].1.trim();
+    if !first.starts_with("thread '") {
+        return None;
+    }
+    let (_, after) = first.split_once("' panicked at ")?;
+
+    let mut j = i + 1;
+    let message = match after.strip_prefix('\'') {
+        Some(old) => old.rsplit_once("', ").map_or(old, |(m, _)| m).to_owned(),
+        None => {
+            j += 1;
+            lines.get(i + 1).map(|l| l.1.to_owned()).unwrap_or_default()
+        }
+    };
+
+    let mut frames = vec![];
+    while j < lines.len() {
+        let line = lines[j].1.trim();
+        if line.starts_with("note: ") || line == "stack backtrace:" {
+            j += 1;
+            continue;
+        }
+
+        let Some((n, function)) = line.split_once(": ") else {
+            break;
+        };
+        if n.parse::<usize>().is_err() {
+            break;
+        }
+        j += 1;
+
+        let loc = lines.get(j).and_then(|l| l.1.trim().strip_prefix("at "));
+        if loc.is_some() {
+            j += 1;
+        }
+
+        // the hash suffix of mangled names and the panic machinery are noise
+        let function = match function.rsplit_once("::h") {
+            Some((f, hash)) if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) => f,
+            _ => function,
+        };
+        let internal = ["std::", "core::", "rust_begin_unwind", "__rust"];
+        if !internal.iter().any(|p| function.starts_with(p)) {
+            frames.push(frame(function, loc));
+        }
+    }
+
+    Some(trace(lines, i, j.min(lines.len()), TraceLang::Rust, &message, frames))
+}
+
+/// Whether `s` is a `path:line:col` source location.
+fn is_location(s: &str) -> bool {
+    let mut parts = s.rsplitn(3, ':');
+    let numbers = parts
+        .by_ref()
+        .take(2)
+        .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        .count();
+    let path = parts.next().unwrap_or_default();
+    numbers == 2 && !path.is_empty() && !path.contains(char::is_whitespace)
+}
+
+/// A `Type: message` line followed by `at ...` frames, as written by Java,
+/// JavaScript and .NET. The frames tell the three apart.
+fn at_trace(lines: &[Line], i: usize) -> Option<(StackTrace, usize)> {
+    let header = lines[i].1.trim();
+    let message = header
+        .strip_prefix("Unhandled exception. ")
+        .or_else(|| {
+            header
+                .strip_prefix("Exception in thread ")
+                .and_then(|h| h.split_once("\" ").map(|(_, m)| m))
+        })
+        .unwrap_or(header);
+    let kind = message.split(':').next().unwrap_or_default();
+    if kind.is_empty()
+        || !kind
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '$'))
+    {
+        return None;
+    }
+
+    let mut lang = None;
+    let mut frames = vec![];
+    let mut j = i + 1;
+    while j < lines.len() {
+        let line = lines[j].1.trim();
+        if line.starts_with("Caused by: ")
+            || line.starts_with("--- End of")
+            || (line.starts_with("... ") && line.ends_with(" more"))
+        {
+            j += 1;
+            continue;
+        }
+        let Some(call) = line.strip_prefix("at ") else {
+            break;
+        };
+        let (frame_lang, f) = if let Some((function, loc)) = call.split_once(") in ") {
+            // .NET: `at Ns.Type.Method(args) in C:\src\File.cs:line 42`
+            let function = function.rsplit_once('(').map_or(function, |(f, _)| f);
+            (TraceLang::DotNet, frame(function, Some(loc)))
+        } else if let Some((function, loc)) = call.strip_suffix(')').and_then(|c| c.rsplit_once(" (")) {
+            // JavaScript: `at fn (/app/index.js:3:9)`
+            (TraceLang::JavaScript, frame(function, Some(loc)))
+        } else if let Some((function, loc)) = call.strip_suffix(')').and_then(|c| c.rsplit_once('(')) {
+            // Java: `at com.example.Main.run(Main.java:12)`, .NET without symbols
+            let java = loc.contains(".java:") || matches!(loc, "Native Method" | "Unknown Source");
+            let lang = if java { TraceLang::Java } else { TraceLang::DotNet };
+            (lang, frame(function, java.then_some(loc).filter(|l| l.contains(':'))))
+        } else if is_location(call) {
+            // JavaScript: `at /app/index.js:3:9`
+            (TraceLang::JavaScript, frame("<anonymous>", Some(call)))
+        } else {
+            // prose like `at least on my machine`
+            break;
+        };
+        j += 1;
+        lang.get_or_insert(frame_lang);
+        frames.push(f);
+    }
+
+    Some(trace(lines, i, j, lang?, message, frames))
+}